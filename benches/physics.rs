@@ -0,0 +1,130 @@
+//! Benchmarks for the parts of the engine which run during real-time playback
+//! and editing. Run with `cargo bench`.
+//!
+//! The line grid is private, so it is benchmarked through the [`Track`] methods
+//! which wrap it.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use bosh_rs::physics::advance_frame::frame_after;
+use bosh_rs::rider::Entity;
+use bosh_rs::{Line, Track, Vector2D};
+
+/// The number of lines in the synthetic track.
+const LINE_COUNT: usize = 10_000;
+/// How many frames the playback benchmark simulates.
+const FRAMES: usize = 10_000;
+
+/// A long, bumpy downhill run made of `LINE_COUNT` connected lines, with a
+/// boshsled starting at the top.
+fn synthetic_track() -> Track {
+    let lines = (0..LINE_COUNT)
+        .map(|i| {
+            let start = ground_at(i);
+            let end = ground_at(i + 1);
+            Line::builder()
+                .point(start.0, start.1)
+                .point(end.0, end.1)
+                .build()
+        })
+        .collect();
+
+    Track::new(
+        vec![Entity::boshsled_at(
+            Vector2D(10.0, -20.0),
+            Vector2D(0.4, 0.0),
+            0.0,
+        )],
+        lines,
+    )
+}
+
+/// The start of the `i`th line of the synthetic track. Every fifth line is a
+/// small bump.
+fn ground_at(i: usize) -> Vector2D {
+    let bump = if i.is_multiple_of(5) { -3.0 } else { 0.0 };
+    Vector2D(i as f64 * 30.0, i as f64 * 6.0 + bump)
+}
+
+fn bench_frame_after(c: &mut Criterion) {
+    let track = synthetic_track();
+    let riders = track.entity_positions_at(500);
+
+    c.bench_function("frame_after", |b| {
+        b.iter(|| frame_after(black_box(&riders), &track))
+    });
+}
+
+fn bench_lines_near(c: &mut Criterion) {
+    let track = synthetic_track();
+    let points: Vec<Vector2D> = (0..LINE_COUNT)
+        .step_by(97)
+        .map(|i| ground_at(i) + Vector2D(7.0, -2.0))
+        .collect();
+
+    c.bench_function("lines_near", |b| {
+        b.iter(|| {
+            for &point in &points {
+                black_box(track.lines_near(point));
+            }
+        })
+    });
+}
+
+fn bench_add_remove_line(c: &mut Criterion) {
+    let track = synthetic_track();
+    let line = Line::builder()
+        .point(50_000.0, 9_000.0)
+        .point(50_100.0, 9_050.0)
+        .build();
+    let mut with_line = track.clone();
+    with_line.add_line(line);
+
+    c.bench_function("add_line", |b| {
+        b.iter_batched(
+            || track.clone(),
+            |mut track| {
+                track.add_line(black_box(line));
+                track
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("remove_line", |b| {
+        b.iter_batched(
+            || with_line.clone(),
+            |mut track| {
+                track.remove_line(black_box(&line));
+                track
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_entity_positions_at(c: &mut Criterion) {
+    let track = synthetic_track();
+
+    let mut group = c.benchmark_group("entity_positions_at");
+    group.sample_size(10);
+    group.bench_function("10k_frames", |b| {
+        b.iter_batched(
+            || track.clone(),
+            |track| {
+                black_box(track.entity_positions_at(FRAMES));
+                track
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_frame_after,
+    bench_lines_near,
+    bench_add_remove_line,
+    bench_entity_positions_at
+);
+criterion_main!(benches);
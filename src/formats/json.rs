@@ -0,0 +1,299 @@
+//! The `.track.json` format used by [linerider.com](https://linerider.com).
+
+use std::io::{Read, Write};
+
+use anyhow::{bail, ensure, Context};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::formats::{rider_start, track_with_line_ids, DEFAULT_START_VELOCITY};
+use crate::game::{Line, LineId, LineType, Track, TrackMeta, Vector2D};
+use crate::linestore::grid::GridVersion;
+use crate::physics::entity_physics::{DEFAULT_GRAVITY, DEFAULT_ITERATIONS};
+use crate::rider::Entity;
+
+const NORMAL_LINE: u8 = 0;
+const ACCELERATE_LINE: u8 = 1;
+const SCENERY_LINE: u8 = 2;
+
+/// A track as stored in linerider.com's `.track.json` files.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonTrack {
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub creator: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub duration: u64,
+    #[serde(default = "default_version")]
+    pub version: String,
+    #[serde(default)]
+    pub start_position: JsonVector,
+    #[serde(default)]
+    pub riders: Vec<JsonRider>,
+    #[serde(default)]
+    pub lines: Vec<JsonLine>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub struct JsonVector {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonRider {
+    pub start_position: JsonVector,
+    pub start_velocity: JsonVector,
+    #[serde(
+        default,
+        serialize_with = "serialize_remountable",
+        deserialize_with = "deserialize_remountable"
+    )]
+    pub remountable: bool,
+    /// How far the rider is turned around its start position, in degrees. This is
+    /// not part of linerider.com's format, so it is only written for turned riders.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub start_angle: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonLine {
+    pub id: u64,
+    #[serde(rename = "type")]
+    pub line_type: u8,
+    pub x1: f64,
+    pub y1: f64,
+    pub x2: f64,
+    pub y2: f64,
+    #[serde(default)]
+    pub flipped: bool,
+    #[serde(default)]
+    pub left_extended: bool,
+    #[serde(default)]
+    pub right_extended: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiplier: Option<u64>,
+}
+
+impl From<Vector2D> for JsonVector {
+    fn from(vec: Vector2D) -> Self {
+        JsonVector { x: vec.0, y: vec.1 }
+    }
+}
+
+impl From<JsonVector> for Vector2D {
+    fn from(vec: JsonVector) -> Self {
+        Vector2D(vec.x, vec.y)
+    }
+}
+
+impl TryFrom<&Track> for JsonTrack {
+    type Error = anyhow::Error;
+
+    /// Fails if the track has physics settings which linerider.com cannot store.
+    fn try_from(track: &Track) -> Result<Self, Self::Error> {
+        let meta = &track.meta;
+        ensure!(
+            meta.gravity == DEFAULT_GRAVITY,
+            ".track.json files cannot store a gravity of {:?}",
+            meta.gravity
+        );
+        ensure!(
+            meta.iterations == DEFAULT_ITERATIONS,
+            ".track.json files cannot store {} iterations",
+            meta.iterations
+        );
+        ensure!(
+            meta.friction_scale == 1.0,
+            ".track.json files cannot store a friction scale of {}",
+            meta.friction_scale
+        );
+
+        let riders: Vec<JsonRider> = track
+            .entity_positions_at(0)
+            .iter()
+            .filter_map(rider_start)
+            .map(|(position, velocity, rotation)| JsonRider {
+                start_position: position.into(),
+                start_velocity: velocity.into(),
+                remountable: track.meta.remount,
+                start_angle: rotation.to_degrees(),
+            })
+            .collect();
+
+        let lines = track
+            .lines_with_ids()
+            .map(|(id, line)| JsonLine::from_line(line, id))
+            .collect();
+
+        Ok(JsonTrack {
+            label: Default::default(),
+            creator: Default::default(),
+            description: Default::default(),
+            duration: 0,
+            version: version_string(track.meta.grid_version).to_string(),
+            start_position: riders.first().map(|r| r.start_position).unwrap_or_default(),
+            riders,
+            lines,
+        })
+    }
+}
+
+impl TryFrom<JsonTrack> for Track {
+    type Error = anyhow::Error;
+
+    fn try_from(json: JsonTrack) -> Result<Self, Self::Error> {
+        let mut meta = TrackMeta {
+            grid_version: parse_version(&json.version)?,
+            ..Default::default()
+        };
+
+        let riders = if json.riders.is_empty() {
+            // tracks saved before multi-rider support only have a start position
+            vec![Entity::boshsled_at(
+                json.start_position.into(),
+                DEFAULT_START_VELOCITY,
+                0.0,
+            )]
+        } else {
+            meta.remount = json.riders.iter().any(|r| r.remountable);
+            json.riders
+                .iter()
+                .map(|r| {
+                    Entity::boshsled_at(
+                        r.start_position.into(),
+                        r.start_velocity.into(),
+                        r.start_angle.to_radians(),
+                    )
+                })
+                .collect()
+        };
+
+        let lines = json
+            .lines
+            .iter()
+            .map(|line| {
+                let built = line
+                    .into_line(&meta)
+                    .with_context(|| format!("error in line {}", line.id))?;
+                Ok((Some(LineId(line.id)), built))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        track_with_line_ids(riders, lines, meta)
+    }
+}
+
+impl JsonLine {
+    fn from_line(line: &Line, id: LineId) -> JsonLine {
+        let (line_type, multiplier) = match line.line_type {
+            LineType::Normal => (NORMAL_LINE, None),
+            LineType::Accelerate { amount } => (ACCELERATE_LINE, Some(amount)),
+            LineType::Scenery => (SCENERY_LINE, None),
+        };
+
+        JsonLine {
+            id: id.0,
+            line_type,
+            x1: line.ends.0.location.0,
+            y1: line.ends.0.location.1,
+            x2: line.ends.1.location.0,
+            y2: line.ends.1.location.1,
+            flipped: line.flipped,
+            left_extended: line.ends.0.extended,
+            right_extended: line.ends.1.extended,
+            multiplier,
+        }
+    }
+
+    fn into_line(self, meta: &TrackMeta) -> anyhow::Result<Line> {
+        let line_type = match self.line_type {
+            NORMAL_LINE => LineType::Normal,
+            ACCELERATE_LINE => LineType::Accelerate {
+                amount: self.multiplier.unwrap_or(1),
+            },
+            SCENERY_LINE => LineType::Scenery,
+            unknown => bail!("invalid line type {unknown}"),
+        };
+
+        Ok(Line::builder()
+            .extension_ratio(meta.line_extension_ratio)
+            .line_type(line_type)
+            .flipped(self.flipped)
+            .point(self.x1, self.y1)
+            .extended(self.left_extended)
+            .point(self.x2, self.y2)
+            .extended(self.right_extended)
+            .build())
+    }
+}
+
+impl Track {
+    /// Reads a track from linerider.com's `.track.json` format.
+    pub fn read_json(input: impl Read) -> anyhow::Result<Track> {
+        let json: JsonTrack = serde_json::from_reader(input).context("invalid track json")?;
+
+        json.try_into()
+    }
+
+    /// Writes the track in linerider.com's `.track.json` format.
+    ///
+    /// Fails if the track's gravity, iterations or friction scale are not the
+    /// defaults, as the format has no way to store them.
+    pub fn write_json(&self, output: impl Write) -> anyhow::Result<()> {
+        serde_json::to_writer(output, &JsonTrack::try_from(self)?)?;
+
+        Ok(())
+    }
+}
+
+fn default_version() -> String {
+    version_string(GridVersion::V6_2).to_string()
+}
+
+fn version_string(version: GridVersion) -> &'static str {
+    match version {
+        GridVersion::V6_1 => "6.1",
+        GridVersion::V6_2 => "6.2",
+    }
+}
+
+fn parse_version(version: &str) -> anyhow::Result<GridVersion> {
+    Ok(match version {
+        "6.0" => bail!("the 6.0 grid is not implemented, only 6.1 and 6.2 tracks can be loaded"),
+        "6.1" => GridVersion::V6_1,
+        "6.2" => GridVersion::V6_2,
+        unknown => bail!("unsupported track version {unknown:?}"),
+    })
+}
+
+fn is_zero(n: &f64) -> bool {
+    *n == 0.0
+}
+
+/// linerider.com stores `remountable` as a number, but older tracks use a bool.
+fn deserialize_remountable<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Remountable {
+        Bool(bool),
+        Number(u8),
+    }
+
+    Ok(match Remountable::deserialize(deserializer)? {
+        Remountable::Bool(b) => b,
+        Remountable::Number(n) => n != 0,
+    })
+}
+
+fn serialize_remountable<S: Serializer>(
+    remountable: &bool,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u8(*remountable as u8)
+}
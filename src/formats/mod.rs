@@ -0,0 +1,764 @@
+pub mod json;
+mod native;
+mod trk;
+
+pub use native::TRACK_FORMAT_VERSION;
+
+use anyhow::{ensure, Context};
+
+use crate::game::{Line, LineId, Track, TrackMeta, Vector2D};
+use crate::rider::{Entity, PointIndex};
+
+/// The velocity that riders start with in both LRA and linerider.com.
+const DEFAULT_START_VELOCITY: Vector2D = Vector2D(0.4, 0.0);
+
+/// Creates a track from lines which may or may not have ids in the file they were
+/// read from. Lines without an id are given one that no other line uses.
+///
+/// Fails if any rider point or line end is not finite, since such points cannot be
+/// placed in the line grid.
+fn track_with_line_ids(
+    riders: Vec<Entity>,
+    lines: Vec<(Option<LineId>, Line)>,
+    meta: TrackMeta,
+) -> anyhow::Result<Track> {
+    let mut next_id = lines
+        .iter()
+        .filter_map(|(id, _)| *id)
+        .max()
+        .map_or(Some(0), |id| id.0.checked_add(1));
+
+    for rider in &riders {
+        ensure!(
+            rider
+                .points
+                .values()
+                .all(|p| p.location.is_finite() && p.previous_location.is_finite()),
+            "rider {} has a point at a non-finite location",
+            rider.id.0
+        );
+    }
+
+    let mut track = Track::new_with_meta(riders, vec![], meta);
+    for (id, line) in lines {
+        ensure!(
+            line.ends.0.location.is_finite() && line.ends.1.location.is_finite(),
+            "line {} has a non-finite end",
+            id.map_or("without an id".to_string(), |id| id.0.to_string())
+        );
+        let id = match id {
+            Some(id) => id,
+            None => {
+                let id = next_id.context("no line ids left for lines without an id")?;
+                next_id = id.checked_add(1);
+                LineId(id)
+            }
+        };
+        track
+            .add_line_with_id(id, line)
+            .with_context(|| format!("duplicate line id {}", id.0))?;
+    }
+
+    Ok(track)
+}
+
+/// Returns the location and momentum of a rider's sled peg, which is what track
+/// files store as a rider's start, along with how far the sled is turned from
+/// the sled of [`Entity::default_boshsled`], in radians.
+fn rider_start(rider: &Entity) -> Option<(Vector2D, Vector2D, f64)> {
+    let peg = rider.points.get(&PointIndex::SledPeg)?;
+    let tail = rider.points.get(&PointIndex::SledTail)?;
+
+    let default = Entity::default_boshsled();
+    let default_tail = default.point_at(PointIndex::SledTail).location
+        - default.point_at(PointIndex::SledPeg).location;
+    let rotation = default_tail.angle_between(tail.location - peg.location);
+
+    Some((peg.location, peg.momentum, rotation))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formats::json::JsonTrack;
+    use crate::game::{
+        Line, LineId, LineType, Track, TrackMeta, Trigger, TriggerAction, TriggerCondition,
+        Vector2D,
+    };
+    use crate::rider::{Entity, PointIndex};
+    use crate::test_tracks::rider_track;
+    use crate::GridVersion;
+
+    fn push_short_string(buf: &mut Vec<u8>, s: &str) {
+        buf.extend((s.len() as i16).to_le_bytes());
+        buf.extend(s.as_bytes());
+    }
+
+    fn push_point(buf: &mut Vec<u8>, x: f64, y: f64) {
+        buf.extend(x.to_le_bytes());
+        buf.extend(y.to_le_bytes());
+    }
+
+    /// A hand-assembled track with one of each line type, laid out the way LRA's
+    /// `TRKWriter` saves it.
+    fn sample_trk() -> Vec<u8> {
+        let mut buf = b"TRK\xF2".to_vec();
+        buf.push(1);
+        push_short_string(&mut buf, "REDMULTIPLIER;SCENERYWIDTH;6.1;REMOUNT;");
+        push_point(&mut buf, 100.0, -50.0);
+        buf.extend(3i32.to_le_bytes());
+
+        // normal line, flipped, extended on both sides
+        buf.push(0b1110_0001);
+        buf.extend(1i32.to_le_bytes());
+        buf.extend((-1i32).to_le_bytes());
+        buf.extend((-1i32).to_le_bytes());
+        push_point(&mut buf, 0.0, 0.0);
+        push_point(&mut buf, 50.0, 10.0);
+
+        // accelerate line, extended on the right side
+        buf.push(0b0100_0010);
+        buf.push(3);
+        buf.extend(2i32.to_le_bytes());
+        buf.extend((-1i32).to_le_bytes());
+        buf.extend((-1i32).to_le_bytes());
+        push_point(&mut buf, 50.0, 10.0);
+        push_point(&mut buf, 90.0, 30.0);
+
+        // scenery line, 2.5 wide
+        buf.push(0);
+        buf.push(25);
+        push_point(&mut buf, -5.0, -5.0);
+        push_point(&mut buf, 5.0, 5.0);
+
+        buf.extend(b"META");
+        buf.extend(3i16.to_le_bytes());
+        push_short_string(&mut buf, "GRAVITYWELLSIZE=5");
+        push_short_string(&mut buf, "XGRAVITY=0.5");
+        push_short_string(&mut buf, "YGRAVITY=2");
+
+        buf
+    }
+
+    #[test]
+    fn read_trk_lines() {
+        let track = Track::read_trk(sample_trk().as_slice()).unwrap();
+
+        assert_eq!(
+            track.all_lines(),
+            &vec![
+                Line::builder()
+                    .flipped(true)
+                    .point(0.0, 0.0)
+                    .extended(true)
+                    .point(50.0, 10.0)
+                    .extended(true)
+                    .build(),
+                Line::builder()
+                    .line_type(LineType::Accelerate { amount: 3 })
+                    .point(50.0, 10.0)
+                    .point(90.0, 30.0)
+                    .extended(true)
+                    .build(),
+                Line::builder()
+                    .line_type(LineType::Scenery)
+                    .point(-5.0, -5.0)
+                    .point(5.0, 5.0)
+                    .build(),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_trk_meta_and_rider() {
+        let track = Track::read_trk(sample_trk().as_slice()).unwrap();
+
+        assert!(track.meta.remount);
+        assert_eq!(track.meta.gravity_well_height, 5.0);
+        assert_eq!(track.meta.gravity, Vector2D(0.0875, 0.35));
+        assert_eq!(track.meta.grid_version, GridVersion::V6_1);
+
+        let riders = track.entity_positions_at(0);
+        assert_eq!(riders.len(), 1);
+        assert_eq!(
+            riders[0].point_at(PointIndex::SledPeg).location,
+            Vector2D(100.0, -50.0)
+        );
+    }
+
+    #[test]
+    fn read_trk_rejects_bad_input() {
+        assert!(Track::read_trk(&b"TRK\x00\x01"[..]).is_err());
+
+        let mut unknown_feature = b"TRK\xF2\x01".to_vec();
+        push_short_string(&mut unknown_feature, "SOMETHINGNEW");
+        assert!(Track::read_trk(unknown_feature.as_slice()).is_err());
+
+        let mut truncated = sample_trk();
+        truncated.truncate(60);
+        assert!(Track::read_trk(truncated.as_slice()).is_err());
+    }
+
+    /// A `.trk` file with no features and `line_count` lines, all going from
+    /// `p1` to (10, 10), with the sled starting at `start`.
+    fn trk_with_lines(start: (f64, f64), line_count: i32, p1: (f64, f64)) -> Vec<u8> {
+        let mut buf = b"TRK\xF2\x01".to_vec();
+        push_short_string(&mut buf, "");
+        push_point(&mut buf, start.0, start.1);
+        buf.extend(line_count.to_le_bytes());
+        for id in 0..line_count.min(4) {
+            buf.push(0b0000_0001);
+            buf.extend(id.to_le_bytes());
+            push_point(&mut buf, p1.0, p1.1);
+            push_point(&mut buf, 10.0, 10.0);
+        }
+
+        buf
+    }
+
+    #[test]
+    fn read_trk_rejects_corrupt_numbers() {
+        assert!(Track::read_trk(trk_with_lines((0.0, 0.0), 1, (0.0, 0.0)).as_slice()).is_ok());
+
+        // a huge line count fails on the missing lines instead of allocating them
+        let huge_count = trk_with_lines((0.0, 0.0), i32::MAX, (0.0, 0.0));
+        assert!(Track::read_trk(huge_count.as_slice()).is_err());
+
+        for bad in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let bad_line = trk_with_lines((0.0, 0.0), 1, (bad, 0.0));
+            assert!(Track::read_trk(bad_line.as_slice()).is_err());
+
+            let bad_start = trk_with_lines((0.0, bad), 1, (0.0, 0.0));
+            assert!(Track::read_trk(bad_start.as_slice()).is_err());
+        }
+
+        let mut bad_gravity = sample_trk();
+        bad_gravity.truncate(bad_gravity.len() - "YGRAVITY=2".len() - 2);
+        push_short_string(&mut bad_gravity, "YGRAVITY=NaN");
+        assert!(Track::read_trk(bad_gravity.as_slice()).is_err());
+    }
+
+    #[test]
+    fn trk_round_trip() {
+        let track = Track::read_trk(sample_trk().as_slice()).unwrap();
+
+        let mut written = vec![];
+        track.write_trk(&mut written).unwrap();
+        let reread = Track::read_trk(written.as_slice()).unwrap();
+
+        assert_eq!(track.all_lines(), reread.all_lines());
+        assert_eq!(track.meta.remount, reread.meta.remount);
+        assert_eq!(
+            track.meta.gravity_well_height,
+            reread.meta.gravity_well_height
+        );
+        assert_eq!(track.meta.gravity, reread.meta.gravity);
+        assert_eq!(track.entity_positions_at(0), reread.entity_positions_at(0));
+    }
+
+    #[test]
+    fn trk_round_trip_zero_start() {
+        let mut rider = Entity::default_boshsled();
+        rider.mutate_points(|p| {
+            p.previous_location = p.location;
+            p.momentum = Vector2D(0.0, 0.0);
+        });
+        let track = Track::new(
+            vec![rider],
+            vec![Line::builder()
+                .line_type(LineType::Accelerate { amount: 200 })
+                .point(-10.0, 20.0)
+                .extended(true)
+                .point(10.0, 20.0)
+                .build()],
+        );
+
+        let mut written = vec![];
+        track.write_trk(&mut written).unwrap();
+        let reread = Track::read_trk(written.as_slice()).unwrap();
+
+        assert_eq!(track.all_lines(), reread.all_lines());
+        assert_eq!(
+            reread.entity_positions_at(0)[0]
+                .point_at(PointIndex::BoshButt)
+                .previous_location,
+            Vector2D(5.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn trk_round_trip_frictionless() {
+        let track = rider_track(Entity::default_boshsled(), vec![], |meta| {
+            meta.friction_scale = 0.0
+        });
+
+        let mut written = vec![];
+        track.write_trk(&mut written).unwrap();
+        let reread = Track::read_trk(written.as_slice()).unwrap();
+
+        assert_eq!(0.0, reread.meta.friction_scale);
+    }
+
+    #[test]
+    fn write_trk_line_type_codes() {
+        let line = |line_type| {
+            Line::builder()
+                .line_type(line_type)
+                .point(0.0, 0.0)
+                .point(10.0, 0.0)
+                .build()
+        };
+        let track = Track::new(
+            vec![],
+            vec![
+                line(LineType::Scenery),
+                line(LineType::Normal),
+                line(LineType::Accelerate { amount: 1 }),
+            ],
+        );
+
+        let mut written = vec![];
+        track.write_trk(&mut written).unwrap();
+
+        // skip the header, features, start position and line count
+        let features_len = i16::from_le_bytes([written[5], written[6]]) as usize;
+        let lines = &written[7 + features_len + 16 + 4..];
+        // scenery: flags, width, two points
+        assert_eq!(0, lines[0]);
+        let lines = &lines[2 + 32..];
+        // normal: flags, id, two points
+        assert_eq!(1, lines[0]);
+        let lines = &lines[1 + 4 + 32..];
+        // accelerate: flags, multiplier, ...
+        assert_eq!(2, lines[0]);
+    }
+
+    #[test]
+    fn writers_reject_unsupported_physics() {
+        let iterations = rider_track(Entity::default_boshsled(), vec![], |meta| {
+            meta.iterations = 3
+        });
+        assert!(iterations.write_trk(&mut vec![]).is_err());
+        assert!(iterations.write_json(&mut vec![]).is_err());
+
+        let friction = rider_track(Entity::default_boshsled(), vec![], |meta| {
+            meta.friction_scale = 0.5
+        });
+        assert!(friction.write_trk(&mut vec![]).is_err());
+        assert!(friction.write_json(&mut vec![]).is_err());
+
+        let frictionless = rider_track(Entity::default_boshsled(), vec![], |meta| {
+            meta.friction_scale = 0.0
+        });
+        assert!(frictionless.write_json(&mut vec![]).is_err());
+
+        let sideways = rider_track(Entity::default_boshsled(), vec![], |meta| {
+            meta.gravity = Vector2D(0.175, 0.0)
+        });
+        assert!(sideways.write_json(&mut vec![]).is_err());
+
+        let mut written = vec![];
+        sideways.write_trk(&mut written).unwrap();
+        assert_eq!(
+            Vector2D(0.175, 0.0),
+            Track::read_trk(written.as_slice()).unwrap().meta.gravity
+        );
+    }
+
+    #[test]
+    fn write_trk_rejects_large_multiplier() {
+        let track = Track::new(
+            vec![],
+            vec![Line::builder()
+                .line_type(LineType::Accelerate { amount: 256 })
+                .point(0.0, 0.0)
+                .point(10.0, 0.0)
+                .build()],
+        );
+
+        assert!(track.write_trk(&mut vec![]).is_err());
+    }
+
+    const SAMPLE_JSON: &str = r#"{
+        "label": "test track",
+        "creator": "",
+        "description": "",
+        "duration": 1200,
+        "version": "6.2",
+        "audio": null,
+        "startPosition": { "x": 0, "y": 0 },
+        "riders": [
+            {
+                "startPosition": { "x": 20, "y": -10 },
+                "startVelocity": { "x": 0.4, "y": 0 },
+                "remountable": 1
+            },
+            {
+                "startPosition": { "x": -50, "y": 0 },
+                "startVelocity": { "x": 0, "y": 1 },
+                "remountable": 1
+            }
+        ],
+        "lines": [
+            {
+                "id": 1, "type": 0,
+                "x1": 0, "y1": 10, "x2": 100, "y2": 30,
+                "flipped": true, "leftExtended": true, "rightExtended": false
+            },
+            {
+                "id": 2, "type": 1,
+                "x1": 100, "y1": 30, "x2": 150, "y2": 30,
+                "flipped": false, "leftExtended": false, "rightExtended": true,
+                "multiplier": 2
+            },
+            { "id": 3, "type": 2, "x1": -5, "y1": -5, "x2": 5, "y2": 5 }
+        ],
+        "layers": []
+    }"#;
+
+    #[test]
+    fn read_json() {
+        let track = Track::read_json(SAMPLE_JSON.as_bytes()).unwrap();
+
+        assert!(track.meta.remount);
+        assert_eq!(
+            track.all_lines(),
+            &vec![
+                Line::builder()
+                    .flipped(true)
+                    .point(0.0, 10.0)
+                    .extended(true)
+                    .point(100.0, 30.0)
+                    .build(),
+                Line::builder()
+                    .line_type(LineType::Accelerate { amount: 2 })
+                    .point(100.0, 30.0)
+                    .point(150.0, 30.0)
+                    .extended(true)
+                    .build(),
+                Line::builder()
+                    .line_type(LineType::Scenery)
+                    .point(-5.0, -5.0)
+                    .point(5.0, 5.0)
+                    .build(),
+            ]
+        );
+
+        let riders = track.entity_positions_at(0);
+        assert_eq!(riders.len(), 2);
+        let peg = riders[1].point_at(PointIndex::SledPeg);
+        assert_eq!(peg.location, Vector2D(-50.0, 0.0));
+        assert_eq!(peg.momentum, Vector2D(0.0, 1.0));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let track = Track::read_json(SAMPLE_JSON.as_bytes()).unwrap();
+
+        let mut written = vec![];
+        track.write_json(&mut written).unwrap();
+        let reread = Track::read_json(written.as_slice()).unwrap();
+
+        assert_eq!(track.all_lines(), reread.all_lines());
+        assert_eq!(track.meta.remount, reread.meta.remount);
+        assert_eq!(track.entity_positions_at(0), reread.entity_positions_at(0));
+    }
+
+    #[test]
+    fn json_turned_riders() {
+        let json = r#"{
+            "version": "6.2",
+            "riders": [
+                { "startPosition": { "x": 0, "y": 0 }, "startVelocity": { "x": 0.4, "y": 0 } },
+                {
+                    "startPosition": { "x": 30, "y": 0 },
+                    "startVelocity": { "x": 0, "y": 0 },
+                    "startAngle": 90
+                }
+            ],
+            "lines": []
+        }"#;
+        let track = Track::read_json(json.as_bytes()).unwrap();
+
+        let riders = track.entity_positions_at(0);
+        let tail = |rider: &Entity| {
+            rider.point_at(PointIndex::SledTail).location
+                - rider.point_at(PointIndex::SledPeg).location
+        };
+        assert_eq!(tail(&riders[0]), Vector2D(0.0, 5.0));
+        assert!((tail(&riders[1]) - Vector2D(-5.0, 0.0)).length_squared() < 1e-18);
+
+        let mut written = vec![];
+        track.write_json(&mut written).unwrap();
+        let written: serde_json::Value = serde_json::from_slice(&written).unwrap();
+        assert!(written["riders"][0].get("startAngle").is_none());
+        assert!((written["riders"][1]["startAngle"].as_f64().unwrap() - 90.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn read_json_without_riders() {
+        let track = Track::read_json(
+            r#"{ "version": "6.1", "startPosition": { "x": 5, "y": 5 }, "lines": [] }"#.as_bytes(),
+        )
+        .unwrap();
+
+        let riders = track.entity_positions_at(0);
+        assert_eq!(riders.len(), 1);
+        assert_eq!(
+            riders[0].point_at(PointIndex::SledPeg).location,
+            Vector2D(5.0, 5.0)
+        );
+    }
+
+    #[test]
+    fn read_json_rejects_6_0_tracks() {
+        let result = Track::read_json(r#"{ "version": "6.0", "lines": [] }"#.as_bytes());
+        let message = format!("{:#}", result.err().unwrap());
+        assert!(message.contains("6.0 grid is not implemented"), "{message}");
+
+        let result =
+            serde_json::from_str::<Track>(r#"{ "version": 2, "meta": { "gridVersion": "6.0" } }"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn read_json_rejects_bad_line_type() {
+        let result = Track::read_json(
+            r#"{ "lines": [{ "id": 1, "type": 7, "x1": 0, "y1": 0, "x2": 1, "y2": 1 }] }"#
+                .as_bytes(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn read_json_rejects_non_finite_points() {
+        let json = r#"{ "lines": [{ "id": 1, "type": 0, "x1": 0, "y1": 0, "x2": 1, "y2": 1 }] }"#;
+        let valid: JsonTrack = serde_json::from_str(json).unwrap();
+        assert!(Track::try_from(valid.clone()).is_ok());
+
+        let mut bad_line = valid.clone();
+        bad_line.lines[0].y2 = f64::NAN;
+        assert!(Track::try_from(bad_line).is_err());
+
+        let mut bad_start = valid;
+        bad_start.start_position.x = f64::INFINITY;
+        assert!(Track::try_from(bad_start).is_err());
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let track = Track::read_json(SAMPLE_JSON.as_bytes()).unwrap();
+
+        let json = serde_json::to_string(&track).unwrap();
+        let reread: Track = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(track.meta, reread.meta);
+        assert_eq!(track.all_lines(), reread.all_lines());
+        assert_eq!(track.entity_positions_at(0), reread.entity_positions_at(0));
+        assert_eq!(
+            track.all_lines()[0].hitbox_extensions(),
+            reread.all_lines()[0].hitbox_extensions()
+        );
+    }
+
+    #[test]
+    fn serde_round_trip_triggers() {
+        let mut track = Track::read_json(SAMPLE_JSON.as_bytes()).unwrap();
+        track.add_trigger(Trigger {
+            condition: TriggerCondition::LineHit(LineId(2)),
+            action: TriggerAction::SetLineType {
+                line: LineId(3),
+                line_type: LineType::Scenery,
+            },
+        });
+
+        let json = serde_json::to_string(&track).unwrap();
+        let reread: Track = serde_json::from_str(&json).unwrap();
+        assert_eq!(track.triggers(), reread.triggers());
+
+        let track: Track = serde_json::from_str(
+            r#"{
+                "version": 3,
+                "triggers": [{ "condition": { "frame": 40 }, "action": { "setGravity": [0.0, 0.1] } }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            track.triggers(),
+            [Trigger {
+                condition: TriggerCondition::Frame(40),
+                action: TriggerAction::SetGravity(Vector2D(0.0, 0.1)),
+            }]
+        );
+    }
+
+    #[test]
+    fn serde_migrates_version_2() {
+        let track: Track = serde_json::from_str(
+            r#"{
+                "version": 2,
+                "meta": { "remount": true, "gridVersion": "6.1" },
+                "lines": [{
+                    "id": 4,
+                    "ends": [{ "location": [0.0, 0.0] }, { "location": [10.0, 0.0] }],
+                    "lineType": "Normal",
+                    "flipped": false
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            track.meta,
+            TrackMeta {
+                remount: true,
+                grid_version: GridVersion::V6_1,
+                ..Default::default()
+            }
+        );
+        assert!(track.triggers().is_empty());
+        assert_eq!(
+            vec![LineId(4)],
+            track.lines_with_ids().map(|(id, _)| id).collect::<Vec<_>>()
+        );
+
+        let written: serde_json::Value = serde_json::to_value(&track).unwrap();
+        assert_eq!(3, written["version"]);
+    }
+
+    #[test]
+    fn serde_rejects_overflowing_line_ids() {
+        let track_with_ids = |ids: &[&str]| {
+            let lines: Vec<String> = ids
+                .iter()
+                .map(|id| {
+                    format!(
+                        r#"{{ {id} "ends": [{{ "location": [0.0, 0.0] }}, {{ "location": [10.0, 0.0] }}],
+                              "lineType": "Normal", "flipped": false }}"#
+                    )
+                })
+                .collect();
+            serde_json::from_str::<Track>(&format!(
+                r#"{{ "version": 3, "lines": [{}] }}"#,
+                lines.join(",")
+            ))
+        };
+
+        let mut largest = track_with_ids(&[r#""id": 18446744073709551615,"#]).unwrap();
+        assert_eq!(largest.add_line(largest.all_lines()[0]), LineId(0));
+
+        // no id is left for the line without one
+        assert!(track_with_ids(&[r#""id": 18446744073709551615,"#, ""]).is_err());
+    }
+
+    #[test]
+    fn serde_defaults_missing_meta() {
+        let track: Track =
+            serde_json::from_str(r#"{ "version": 1, "meta": { "remount": true } }"#).unwrap();
+
+        assert_eq!(
+            track.meta,
+            TrackMeta {
+                remount: true,
+                ..Default::default()
+            }
+        );
+        assert!(track.all_lines().is_empty());
+    }
+
+    #[test]
+    fn serde_rejects_unknown_version() {
+        let result = serde_json::from_str::<Track>(r#"{ "version": 99 }"#);
+
+        assert!(result.is_err());
+    }
+
+    fn ids(track: &Track) -> Vec<LineId> {
+        track.lines_with_ids().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn trk_preserves_line_ids() {
+        let track = Track::read_trk(sample_trk().as_slice()).unwrap();
+        // the scenery line has no id in the file
+        assert_eq!(ids(&track), vec![LineId(1), LineId(2), LineId(3)]);
+
+        let mut written = vec![];
+        track.write_trk(&mut written).unwrap();
+        let reread = Track::read_trk(written.as_slice()).unwrap();
+        assert_eq!(ids(&reread)[..2], [LineId(1), LineId(2)]);
+    }
+
+    #[test]
+    fn json_preserves_line_ids() {
+        let mut track = Track::read_json(SAMPLE_JSON.as_bytes()).unwrap();
+        track.remove_line_by_id(LineId(1));
+        assert_eq!(ids(&track), vec![LineId(3), LineId(2)]);
+
+        let mut written = vec![];
+        track.write_json(&mut written).unwrap();
+        let reread = Track::read_json(written.as_slice()).unwrap();
+        assert_eq!(ids(&reread), vec![LineId(3), LineId(2)]);
+    }
+
+    #[test]
+    fn serde_preserves_line_ids() {
+        let mut track = Track::read_json(SAMPLE_JSON.as_bytes()).unwrap();
+        track.remove_line_by_id(LineId(2));
+
+        let json = serde_json::to_string(&track).unwrap();
+        let reread: Track = serde_json::from_str(&json).unwrap();
+        assert_eq!(ids(&reread), vec![LineId(1), LineId(3)]);
+    }
+
+    #[test]
+    fn read_json_rejects_duplicate_ids() {
+        let result = Track::read_json(
+            r#"{ "lines": [
+                { "id": 1, "type": 0, "x1": 0, "y1": 0, "x2": 1, "y2": 1 },
+                { "id": 1, "type": 0, "x1": 5, "y1": 0, "x2": 6, "y2": 1 }
+            ] }"#
+                .as_bytes(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn grid_version_round_trips() {
+        let mut track = Track::read_json(SAMPLE_JSON.as_bytes()).unwrap();
+        assert_eq!(track.meta.grid_version, GridVersion::V6_2);
+
+        let mut trk = vec![];
+        track.write_trk(&mut trk).unwrap();
+        assert_eq!(
+            Track::read_trk(trk.as_slice()).unwrap().meta.grid_version,
+            GridVersion::V6_2
+        );
+
+        track.set_grid_version(GridVersion::V6_1);
+
+        let mut trk = vec![];
+        track.write_trk(&mut trk).unwrap();
+        assert_eq!(
+            Track::read_trk(trk.as_slice()).unwrap().meta.grid_version,
+            GridVersion::V6_1
+        );
+
+        let mut json = vec![];
+        track.write_json(&mut json).unwrap();
+        assert_eq!(
+            Track::read_json(json.as_slice()).unwrap().meta.grid_version,
+            GridVersion::V6_1
+        );
+
+        let serialized = serde_json::to_string(&track).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Track>(&serialized)
+                .unwrap()
+                .meta
+                .grid_version,
+            GridVersion::V6_1
+        );
+    }
+}
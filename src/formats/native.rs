@@ -0,0 +1,132 @@
+//! bosh-rs' own serde representation of a [`Track`].
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::formats::track_with_line_ids;
+use crate::game::{Line, LineId, Track, TrackMeta, Trigger};
+use crate::rider::Entity;
+
+/// The current version of the serialized [`Track`] format.
+///
+/// A serialized track has the following shape (shown as JSON):
+///
+/// ```json
+/// {
+///     "version": 3,
+///     "meta": { "lineExtensionRatio": 0.25, "gravityWellHeight": 10.0, ... },
+///     "lines": [ { "id": 0, ... }, ... ],
+///     "riders": [ ... ],
+///     "triggers": [ { "condition": { "frame": 40 }, "action": { "setGravity": [0.0, 0.1] } }, ... ]
+/// }
+/// ```
+///
+/// `meta` is a [`TrackMeta`], and each entry of `lines` is a [`Line`] with an
+/// added `id` field. `riders` holds the entities on the first frame of the track;
+/// later frames are not stored since they can be recomputed. `triggers` holds the
+/// [`Trigger`]s of the track, and is left out when there are none. Tracks serialized by
+/// older versions of bosh-rs are migrated when they are deserialized, and missing
+/// `meta` fields take their default values.
+///
+/// Version 2 added line ids, and version 3 added triggers, the physics settings in
+/// `meta` and custom entities with break groups and scarves.
+pub const TRACK_FORMAT_VERSION: u32 = 3;
+
+#[derive(Serialize)]
+struct SerializeTrack<'a> {
+    version: u32,
+    meta: &'a TrackMeta,
+    lines: Vec<SerializeLine<'a>>,
+    riders: Vec<Entity>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    triggers: &'a [Trigger],
+}
+
+#[derive(Serialize)]
+struct SerializeLine<'a> {
+    id: LineId,
+    #[serde(flatten)]
+    line: &'a Line,
+}
+
+#[derive(Deserialize)]
+struct DeserializeTrack {
+    version: u32,
+    #[serde(default)]
+    meta: TrackMeta,
+    #[serde(default)]
+    lines: Vec<DeserializeLine>,
+    #[serde(default)]
+    riders: Vec<Entity>,
+    #[serde(default)]
+    triggers: Vec<Trigger>,
+}
+
+#[derive(Deserialize)]
+struct DeserializeLine {
+    /// Missing in version 1, where lines did not have ids.
+    #[serde(default)]
+    id: Option<LineId>,
+    #[serde(flatten)]
+    line: Line,
+}
+
+impl Serialize for Track {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializeTrack {
+            version: TRACK_FORMAT_VERSION,
+            meta: &self.meta,
+            lines: self
+                .lines_with_ids()
+                .map(|(id, line)| SerializeLine { id, line })
+                .collect(),
+            riders: self.entity_positions_at(0),
+            triggers: self.triggers(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Track {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let track = DeserializeTrack::deserialize(deserializer)?.migrate()?;
+
+        // the extension ratio is not stored per-line
+        let extension_ratio = track.meta.line_extension_ratio;
+        let lines = track
+            .lines
+            .into_iter()
+            .map(|l| (l.id, l.line.with_extension_ratio(extension_ratio)))
+            .collect();
+
+        let mut result =
+            track_with_line_ids(track.riders, lines, track.meta).map_err(D::Error::custom)?;
+        for trigger in track.triggers {
+            result.add_trigger(trigger);
+        }
+
+        Ok(result)
+    }
+}
+
+impl DeserializeTrack {
+    /// Upgrades a track from an older format version to the current one.
+    fn migrate<E: Error>(mut self) -> Result<DeserializeTrack, E> {
+        if self.version == 1 {
+            // lines without ids are assigned one when the track is created
+            self.version = 2;
+        }
+        if self.version == 2 {
+            // tracks without triggers, custom entities or physics settings, all of
+            // which are filled in by their defaults
+            self.version = 3;
+        }
+
+        match self.version {
+            TRACK_FORMAT_VERSION => Ok(self),
+            version => Err(E::custom(format!(
+                "unsupported track format version {version}, expected at most {TRACK_FORMAT_VERSION}"
+            ))),
+        }
+    }
+}
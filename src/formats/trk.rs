@@ -0,0 +1,371 @@
+//! Reading and writing of Linerider Advanced's `.trk` track format.
+//!
+//! All integers and floats are little-endian. The layout is:
+//!
+//!  * the magic bytes `TRK\xF2`
+//!  * a version byte, which must be `1`
+//!  * an `i16`-prefixed, `;`-separated list of feature flags
+//!  * the song info, if the `SONGINFO` feature is present
+//!  * the start position as two `f64`s
+//!  * an `i32` line count, followed by each line record
+//!  * optionally, a `META` section of `key=value` strings
+
+use std::io::{Read, Write};
+
+use anyhow::{bail, ensure, Context};
+use read_from::{LittleEndian, ReadFrom, WriteTo};
+
+use crate::formats::{rider_start, track_with_line_ids, DEFAULT_START_VELOCITY};
+use crate::game::{Line, LineId, LineType, Track, TrackMeta, Vector2D};
+use crate::linestore::grid::GridVersion;
+use crate::physics::entity_physics::{DEFAULT_GRAVITY, DEFAULT_ITERATIONS};
+use crate::rider::Entity;
+
+pub(crate) const TRK_MAGIC: [u8; 4] = *b"TRK\xF2";
+pub(crate) const META_MAGIC: [u8; 4] = *b"META";
+pub(crate) const TRK_VERSION: u8 = 1;
+
+pub(crate) mod features {
+    pub const RED_MULTIPLIER: &str = "REDMULTIPLIER";
+    pub const SCENERY_WIDTH: &str = "SCENERYWIDTH";
+    pub const SIX_ONE: &str = "6.1";
+    pub const SONG_INFO: &str = "SONGINFO";
+    pub const IGNORABLE_TRIGGER: &str = "IGNORABLE_TRIGGER";
+    pub const ZERO_START: &str = "ZEROSTART";
+    pub const REMOUNT: &str = "REMOUNT";
+    pub const FRICTIONLESS: &str = "FRICTIONLESS";
+}
+
+pub(crate) mod metadata {
+    pub const GRAVITY_WELL_SIZE: &str = "GRAVITYWELLSIZE";
+    /// The horizontal gravity, in multiples of the default vertical gravity.
+    pub const X_GRAVITY: &str = "XGRAVITY";
+    /// The vertical gravity, in multiples of the default vertical gravity.
+    pub const Y_GRAVITY: &str = "YGRAVITY";
+}
+
+/// The line type as stored in the lowest 5 bits of a line's flag byte. These are
+/// the values of LRA's `LineType` enum.
+pub(crate) mod line_kind {
+    pub const SCENERY: u8 = 0;
+    pub const NORMAL: u8 = 1;
+    pub const ACCELERATE: u8 = 2;
+}
+
+#[derive(Default)]
+struct Features {
+    red_multiplier: bool,
+    scenery_width: bool,
+    song_info: bool,
+    ignorable_trigger: bool,
+    zero_start: bool,
+}
+
+impl Track {
+    /// Reads a track from Linerider Advanced's `.trk` format.
+    ///
+    /// The rider is spawned as a default boshsled at the track's start position.
+    /// Line triggers, song info and scenery widths are read but discarded, as they
+    /// do not affect physics.
+    pub fn read_trk(mut input: impl Read) -> anyhow::Result<Track> {
+        let magic = <[u8; 4]>::read_from(&mut input).context("error while reading magic")?;
+        if magic != TRK_MAGIC {
+            bail!("not a .trk file: bad magic {magic:?}");
+        }
+
+        let version = u8::read_from(&mut input).context("error while reading version")?;
+        if version != TRK_VERSION {
+            bail!("unsupported .trk version {version}");
+        }
+
+        let mut meta = TrackMeta::default();
+        let mut features = Features::default();
+        let feature_string =
+            read_short_string(&mut input).context("error while reading features")?;
+        for feature in feature_string.split(';').filter(|f| !f.is_empty()) {
+            match feature {
+                features::RED_MULTIPLIER => features.red_multiplier = true,
+                features::SCENERY_WIDTH => features.scenery_width = true,
+                features::SONG_INFO => features.song_info = true,
+                features::IGNORABLE_TRIGGER => features.ignorable_trigger = true,
+                features::ZERO_START => features.zero_start = true,
+                features::REMOUNT => meta.remount = true,
+                features::SIX_ONE => meta.grid_version = GridVersion::V6_1,
+                features::FRICTIONLESS => meta.friction_scale = 0.0,
+                unknown => bail!("unsupported .trk feature {unknown:?}"),
+            }
+        }
+
+        if features.song_info {
+            read_dotnet_string(&mut input).context("error while reading song info")?;
+        }
+
+        let start_position =
+            Vector2D::read_from(&mut input).context("error while reading start position")?;
+
+        let line_count = read_i32(&mut input).context("error while reading line count")?;
+        // the count is not trusted for preallocation, as a corrupt file could claim
+        // billions of lines
+        let mut lines = Vec::new();
+        for i in 0..line_count {
+            let line = read_line(&mut input, &features, &meta)
+                .with_context(|| format!("error while reading line {i}"))?;
+            lines.push(line);
+        }
+
+        read_metadata(&mut input, &mut meta).context("error while reading metadata")?;
+
+        let start_velocity = if features.zero_start {
+            Vector2D(0.0, 0.0)
+        } else {
+            DEFAULT_START_VELOCITY
+        };
+        let rider = Entity::boshsled_at(start_position, start_velocity, 0.0);
+
+        track_with_line_ids(vec![rider], lines, meta)
+    }
+
+    /// Writes the track in Linerider Advanced's `.trk` format.
+    ///
+    /// The format only supports a single, unturned rider, so the start position is
+    /// taken from the sled peg of the first rider. Fails if the track uses a number
+    /// of iterations other than the default, or a friction scale other than 0 or 1,
+    /// as the format has no way to store them.
+    pub fn write_trk(&self, mut output: impl Write) -> anyhow::Result<()> {
+        ensure!(
+            self.meta.iterations == DEFAULT_ITERATIONS,
+            ".trk files cannot store {} iterations",
+            self.meta.iterations
+        );
+        ensure!(
+            self.meta.friction_scale == 0.0 || self.meta.friction_scale == 1.0,
+            ".trk files cannot store a friction scale of {}",
+            self.meta.friction_scale
+        );
+
+        let (start_position, start_velocity) = self
+            .entity_positions_at(0)
+            .first()
+            .and_then(rider_start)
+            .map(|(position, velocity, _)| (position, velocity))
+            .unwrap_or((Vector2D(0.0, 0.0), DEFAULT_START_VELOCITY));
+        let zero_start = start_velocity == Vector2D(0.0, 0.0);
+
+        let mut feature_list = vec![features::RED_MULTIPLIER, features::SCENERY_WIDTH];
+        if zero_start {
+            feature_list.push(features::ZERO_START);
+        }
+        if self.meta.remount {
+            feature_list.push(features::REMOUNT);
+        }
+        if self.meta.friction_scale == 0.0 {
+            feature_list.push(features::FRICTIONLESS);
+        }
+        if self.meta.grid_version == GridVersion::V6_1 {
+            feature_list.push(features::SIX_ONE);
+        }
+
+        output.write_all(&TRK_MAGIC)?;
+        output.write_all(&[TRK_VERSION])?;
+        write_short_string(&mut output, &(feature_list.join(";") + ";"))?;
+        start_position.write_to(&mut output)?;
+
+        LittleEndian(i32::try_from(self.all_lines().len())?).write_to(&mut output)?;
+        for (id, line) in self.lines_with_ids() {
+            write_line(&mut output, line, id)
+                .with_context(|| format!("error while writing line {}", id.0))?;
+        }
+
+        let gravity = self.meta.gravity;
+        let entries = [
+            (metadata::GRAVITY_WELL_SIZE, self.meta.gravity_well_height),
+            (metadata::X_GRAVITY, gravity.0 / DEFAULT_GRAVITY.1),
+            (metadata::Y_GRAVITY, gravity.1 / DEFAULT_GRAVITY.1),
+        ];
+
+        output.write_all(&META_MAGIC)?;
+        LittleEndian(entries.len() as i16).write_to(&mut output)?;
+        for (key, value) in entries {
+            write_short_string(&mut output, &format!("{key}={value}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a line, along with its id if it has one. Scenery lines are not stored with an id.
+fn read_line(
+    input: &mut impl Read,
+    features: &Features,
+    meta: &TrackMeta,
+) -> anyhow::Result<(Option<LineId>, Line)> {
+    let flags = u8::read_from(&mut *input).context("error while reading line flags")?;
+    let kind = flags & 0x1F;
+    let extension = (flags >> 5) & 0b11;
+    let flipped = flags >> 7 != 0;
+
+    let mut multiplier = 1;
+    if features.red_multiplier && kind == line_kind::ACCELERATE {
+        multiplier = u8::read_from(&mut *input).context("error while reading multiplier")?;
+    }
+
+    let line_type = match kind {
+        line_kind::NORMAL => LineType::Normal,
+        line_kind::ACCELERATE => LineType::Accelerate {
+            amount: multiplier as u64,
+        },
+        line_kind::SCENERY => LineType::Scenery,
+        unknown => bail!("invalid line type {unknown}"),
+    };
+
+    let mut id = None;
+    if kind == line_kind::SCENERY {
+        if features.scenery_width {
+            u8::read_from(&mut *input).context("error while reading scenery width")?;
+        }
+    } else {
+        if features.ignorable_trigger {
+            let is_zoom_trigger =
+                u8::read_from(&mut *input).context("error while reading trigger")? != 0;
+            if is_zoom_trigger {
+                // zoom target (f32) and duration in frames (i16)
+                <[u8; 6]>::read_from(&mut *input).context("error while reading zoom trigger")?;
+            }
+        }
+
+        let raw_id = read_i32(&mut *input).context("error while reading line id")?;
+        id = Some(LineId(
+            u64::try_from(raw_id).with_context(|| format!("invalid line id {raw_id}"))?,
+        ));
+        if extension != 0 {
+            // ids of the previous and next lines, only used by old versions of LRA
+            read_i32(&mut *input).context("error while reading previous line id")?;
+            read_i32(&mut *input).context("error while reading next line id")?;
+        }
+    }
+
+    let p1 = Vector2D::read_from(&mut *input).context("error while reading first point")?;
+    let p2 = Vector2D::read_from(&mut *input).context("error while reading second point")?;
+
+    let line = Line::builder()
+        .extension_ratio(meta.line_extension_ratio)
+        .line_type(line_type)
+        .flipped(flipped)
+        .point_vec(p1)
+        .extended(extension & 0b01 != 0)
+        .point_vec(p2)
+        .extended(extension & 0b10 != 0)
+        .build();
+
+    Ok((id, line))
+}
+
+fn write_line(output: &mut impl Write, line: &Line, id: LineId) -> anyhow::Result<()> {
+    let kind = match line.line_type {
+        LineType::Normal => line_kind::NORMAL,
+        LineType::Accelerate { .. } => line_kind::ACCELERATE,
+        LineType::Scenery => line_kind::SCENERY,
+    };
+    let extension = line.ends.0.extended as u8 | (line.ends.1.extended as u8) << 1;
+    let flags = kind | extension << 5 | (line.flipped as u8) << 7;
+    output.write_all(&[flags])?;
+
+    if let LineType::Accelerate { amount } = line.line_type {
+        let multiplier =
+            u8::try_from(amount).with_context(|| format!("multiplier {amount} is too large"))?;
+        output.write_all(&[multiplier])?;
+    }
+
+    if kind == line_kind::SCENERY {
+        // scenery width of 1.0
+        output.write_all(&[10])?;
+    } else {
+        let id = i32::try_from(id.0).with_context(|| format!("line id {} is too large", id.0))?;
+        LittleEndian(id).write_to(&mut *output)?;
+        if extension != 0 {
+            LittleEndian(-1i32).write_to(&mut *output)?;
+            LittleEndian(-1i32).write_to(&mut *output)?;
+        }
+    }
+
+    line.ends.0.location.write_to(&mut *output)?;
+    line.ends.1.location.write_to(&mut *output)?;
+
+    Ok(())
+}
+
+fn read_metadata(input: &mut impl Read, meta: &mut TrackMeta) -> anyhow::Result<()> {
+    let mut magic = [0; 4];
+    let read = input.read(&mut magic)?;
+    if read == 0 {
+        return Ok(());
+    }
+    input.read_exact(&mut magic[read..])?;
+    if magic != META_MAGIC {
+        bail!("expected metadata, got {magic:?}");
+    }
+
+    let count = LittleEndian::<i16>::read_from(&mut *input)?.0;
+    for _ in 0..count {
+        let entry = read_short_string(&mut *input)?;
+        let (key, value) = entry.split_once('=').unwrap_or((&entry, ""));
+
+        let parse = |name| {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .with_context(|| format!("invalid {name} {value:?}"))
+        };
+        match key {
+            metadata::GRAVITY_WELL_SIZE => meta.gravity_well_height = parse("gravity well size")?,
+            metadata::X_GRAVITY => meta.gravity.0 = parse("gravity")? * DEFAULT_GRAVITY.1,
+            metadata::Y_GRAVITY => meta.gravity.1 = parse("gravity")? * DEFAULT_GRAVITY.1,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn read_i32(input: &mut impl Read) -> anyhow::Result<i32> {
+    Ok(LittleEndian::<i32>::read_from(input)?.0)
+}
+
+/// Reads a string prefixed by its length as an `i16`.
+fn read_short_string(input: &mut impl Read) -> anyhow::Result<String> {
+    let len = LittleEndian::<i16>::read_from(&mut *input)?.0;
+    if len < 0 {
+        bail!("negative string length {len}");
+    }
+
+    let mut buf = vec![0; len as usize];
+    input.read_exact(&mut buf)?;
+
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Writes a string prefixed by its length as an `i16`.
+fn write_short_string(output: &mut impl Write, s: &str) -> anyhow::Result<()> {
+    LittleEndian(i16::try_from(s.len())?).write_to(&mut *output)?;
+    output.write_all(s.as_bytes())?;
+
+    Ok(())
+}
+
+/// Reads a string as written by .NET's `BinaryWriter`, which is prefixed by its
+/// length as a 7-bit encoded integer.
+fn read_dotnet_string(input: &mut impl Read) -> anyhow::Result<String> {
+    let mut len = 0usize;
+    for shift in (0..35).step_by(7) {
+        let byte = u8::read_from(&mut *input)?;
+        len |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            let mut buf = vec![0; len];
+            input.read_exact(&mut buf)?;
+            return Ok(String::from_utf8(buf)?);
+        }
+    }
+
+    bail!("string length is too long")
+}
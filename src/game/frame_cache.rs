@@ -0,0 +1,435 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::RwLock;
+
+use crate::game::trigger::LineHits;
+use crate::game::Vector2D;
+use crate::linestore::grid::{GridIndex, GridRegion};
+use crate::rider::Entity;
+use crate::Track;
+
+/// Decides which simulated frames a [`Track`] keeps in memory.
+///
+/// Frames which are not kept are re-simulated from the closest earlier frame
+/// that was kept when they are requested again. The first frame is always kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CachePolicy {
+    /// Keep every frame.
+    #[default]
+    Full,
+    /// Keep every `interval`th frame. An interval of zero keeps only the first frame.
+    Checkpoints { interval: usize },
+    /// Keep the `capacity` most recently requested or simulated frames.
+    Lru { capacity: usize },
+}
+
+impl CachePolicy {
+    fn is_checkpoint(self, frame: usize) -> bool {
+        match self {
+            CachePolicy::Full => true,
+            CachePolicy::Checkpoints { interval } => frame.is_multiple_of(interval),
+            CachePolicy::Lru { .. } => frame == 0,
+        }
+    }
+}
+
+/// The area covered by a single entity's points during a frame.
+#[derive(Clone, Copy, Debug)]
+struct Bounds {
+    min: Vector2D,
+    max: Vector2D,
+}
+
+impl Bounds {
+    fn around(mut points: impl Iterator<Item = Vector2D>) -> Option<Bounds> {
+        let first = points.next()?;
+        Some(points.fold(
+            Bounds {
+                min: first,
+                max: first,
+            },
+            |b, p| Bounds {
+                min: Vector2D(b.min.0.min(p.0), b.min.1.min(p.1)),
+                max: Vector2D(b.max.0.max(p.0), b.max.1.max(p.1)),
+            },
+        ))
+    }
+
+    /// Gets the bounds of each entity in `frame`, along with where the entities
+    /// in `previous` were headed.
+    fn of_frame(previous: &[Entity], frame: &[Entity]) -> Vec<Bounds> {
+        let predicted = previous.iter().filter_map(|e| {
+            Bounds::around(
+                e.points
+                    .values()
+                    .map(|p| p.location * 2.0 - p.previous_location),
+            )
+        });
+        let actual = frame
+            .iter()
+            .filter_map(|e| Bounds::around(e.points.values().map(|p| p.location)));
+
+        predicted.chain(actual).collect()
+    }
+}
+
+/// How many grid cells a single entity may cover in one frame before the frame is
+/// treated as being near every cell.
+const MAX_VISITED_CELLS: usize = 1024;
+
+/// The simulated frames of a track, stored according to a [`CachePolicy`].
+///
+/// Frames can be read and simulated from several threads at once. Simulation
+/// happens outside of the lock, so threads reading frames which are already
+/// stored are not blocked by threads simulating new ones.
+#[derive(Debug)]
+pub(crate) struct FrameCache {
+    policy: CachePolicy,
+    state: RwLock<CacheState>,
+}
+
+#[derive(Clone, Debug)]
+struct CacheState {
+    /// The frames currently kept in memory. Always contains the first frame.
+    frames: BTreeMap<usize, Vec<Entity>>,
+    /// The number of frames simulated since the last invalidation, whether or not
+    /// they were kept.
+    simulated: usize,
+    /// The first frame in which a rider came near each grid cell, for the frames
+    /// simulated since the last invalidation. Grows with the area the riders cover
+    /// rather than with the number of frames.
+    first_visits: HashMap<GridIndex, usize>,
+    /// The first frame in which an entity covered more than [`MAX_VISITED_CELLS`],
+    /// which every edit invalidates.
+    first_wide_frame: Option<usize>,
+    /// Frames kept by the LRU policy, least recently used first.
+    recent: VecDeque<usize>,
+    /// The lines with triggers that were hit in the frames simulated since the last
+    /// invalidation.
+    line_hits: LineHits,
+}
+
+impl FrameCache {
+    pub fn new(starting_positions: Vec<Entity>) -> FrameCache {
+        FrameCache {
+            policy: CachePolicy::Full,
+            state: RwLock::new(CacheState {
+                frames: BTreeMap::from([(0, starting_positions)]),
+                simulated: 1,
+                first_visits: HashMap::new(),
+                first_wide_frame: None,
+                recent: VecDeque::new(),
+                line_hits: LineHits::new(),
+            }),
+        }
+    }
+
+    pub fn policy(&self) -> CachePolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: CachePolicy) {
+        self.policy = policy;
+
+        let state = self.state.get_mut().unwrap();
+        state.frames.retain(|&frame, _| policy.is_checkpoint(frame));
+        state.recent.clear();
+    }
+
+    /// The number of frames simulated since the last invalidation.
+    pub fn simulated_len(&self) -> usize {
+        self.state.read().unwrap().simulated
+    }
+
+    /// The number of frames currently kept in memory.
+    pub fn stored_len(&self) -> usize {
+        self.state.read().unwrap().frames.len()
+    }
+
+    /// The lines with triggers that were hit in the frames simulated so far.
+    pub fn line_hits(&self) -> LineHits {
+        self.state.read().unwrap().line_hits.clone()
+    }
+
+    pub fn first_frame_mut(&mut self) -> &mut Vec<Entity> {
+        self.state.get_mut().unwrap().frames.get_mut(&0).unwrap()
+    }
+
+    /// Gets a frame, simulating it from the closest earlier kept frame if needed.
+    pub fn get(&self, frame: usize, track: &Track) -> Vec<Entity> {
+        self.simulate(frame, track, || false).unwrap()
+    }
+
+    /// Gets a frame only if it is kept in memory. Does not count as a use of the
+    /// frame for the LRU policy.
+    pub fn get_stored(&self, frame: usize) -> Option<Vec<Entity>> {
+        self.state.read().unwrap().frames.get(&frame).cloned()
+    }
+
+    /// Like [`FrameCache::get`], but gives up and returns None as soon as
+    /// `should_stop` returns true between two frames.
+    pub fn simulate(
+        &self,
+        frame: usize,
+        track: &Track,
+        should_stop: impl Fn() -> bool,
+    ) -> Option<Vec<Entity>> {
+        if !matches!(self.policy, CachePolicy::Lru { .. }) {
+            if let Some(riders) = self.state.read().unwrap().frames.get(&frame) {
+                return Some(riders.clone());
+            }
+        }
+
+        let (start, mut current, mut line_hits) = {
+            let mut state = self.state.write().unwrap();
+            if let Some(riders) = state.frames.get(&frame) {
+                let riders = riders.clone();
+                state.touch(self.policy, frame);
+                return Some(riders);
+            }
+
+            let (&start, riders) = state.frames.range(..frame).next_back().unwrap();
+            (start, riders.clone(), state.line_hits.clone())
+        };
+
+        for next_frame in start + 1..=frame {
+            if should_stop() {
+                return None;
+            }
+
+            let next = track.step(&current, next_frame, &mut line_hits, None);
+            let previous = std::mem::replace(&mut current, next);
+
+            let mut state = self.state.write().unwrap();
+            if next_frame == state.simulated {
+                state.visit(next_frame, &previous, &current);
+                for (&line, &hit) in &line_hits {
+                    state.line_hits.entry(line).or_insert(hit);
+                }
+            }
+            if next_frame - 1 != start {
+                state.store(self.policy, next_frame - 1, previous);
+            }
+        }
+
+        self.state
+            .write()
+            .unwrap()
+            .store(self.policy, frame, current.clone());
+        Some(current)
+    }
+
+    /// Forgets every frame starting at `frame`.
+    pub fn invalidate_from(&mut self, frame: usize) {
+        let frame = frame.max(1);
+        let state = self.state.get_mut().unwrap();
+        state.frames.split_off(&frame);
+        state.simulated = state.simulated.min(frame);
+        state.first_visits.retain(|_, &mut visit| visit < frame);
+        state.first_wide_frame = state.first_wide_frame.filter(|&wide| wide < frame);
+        state.recent.retain(|&f| f < frame);
+        state.line_hits.retain(|_, &mut hit| hit < frame);
+    }
+
+    /// Forgets every frame starting at the first frame in which a rider came near
+    /// any of `regions`.
+    pub fn invalidate_near(&mut self, regions: &[GridRegion]) {
+        let state = self.state.get_mut().unwrap();
+        let first_affected = regions
+            .iter()
+            .flat_map(GridRegion::cells)
+            .filter_map(|cell| state.first_visits.get(cell).copied())
+            .chain(state.first_wide_frame)
+            .min();
+
+        if let Some(frame) = first_affected {
+            self.invalidate_from(frame);
+        }
+    }
+}
+
+impl Clone for FrameCache {
+    fn clone(&self) -> Self {
+        FrameCache {
+            policy: self.policy,
+            state: RwLock::new(self.state.read().unwrap().clone()),
+        }
+    }
+}
+
+impl CacheState {
+    /// Records the cells that riders came near while moving from `previous` to
+    /// `frame`, which is the frame numbered `index`.
+    fn visit(&mut self, index: usize, previous: &[Entity], frame: &[Entity]) {
+        for bounds in Bounds::of_frame(previous, frame) {
+            match GridIndex::cells_in_box(bounds.min, bounds.max, MAX_VISITED_CELLS) {
+                Some(cells) => {
+                    for cell in cells {
+                        self.first_visits.entry(cell).or_insert(index);
+                    }
+                }
+                None => {
+                    self.first_wide_frame.get_or_insert(index);
+                }
+            }
+        }
+        self.simulated += 1;
+    }
+
+    fn store(&mut self, policy: CachePolicy, frame: usize, riders: Vec<Entity>) {
+        match policy {
+            CachePolicy::Lru { .. } => {
+                self.frames.insert(frame, riders);
+                self.touch(policy, frame);
+            }
+            policy if policy.is_checkpoint(frame) => {
+                self.frames.insert(frame, riders);
+            }
+            _ => {}
+        }
+    }
+
+    fn touch(&mut self, policy: CachePolicy, frame: usize) {
+        let CachePolicy::Lru { capacity } = policy else {
+            return;
+        };
+        if frame == 0 {
+            return;
+        }
+
+        self.recent.retain(|&f| f != frame);
+        self.recent.push_back(frame);
+        while self.recent.len() > capacity {
+            let evicted = self.recent.pop_front().unwrap();
+            self.frames.remove(&evicted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{CachePolicy, Line, Track};
+    use crate::rider::{Entity, Skeleton};
+    use crate::test_tracks::floor_track;
+
+    #[test]
+    fn far_line_edit_keeps_frames() {
+        let mut track = floor_track();
+        track.entity_positions_at(100);
+
+        track.add_line(
+            Line::builder()
+                .point(5000.0, 5000.0)
+                .point(5100.0, 5000.0)
+                .build(),
+        );
+
+        assert_eq!(101, track.precomputed_frame_count());
+    }
+
+    #[test]
+    fn near_line_edit_invalidates_later_frames() {
+        let mut track = floor_track();
+        track.entity_positions_at(100);
+
+        let bump = Line::builder().point(100.0, 10.0).point(120.0, 0.0).build();
+        track.add_line(bump);
+
+        let remaining = track.precomputed_frame_count();
+        assert!(1 < remaining && remaining < 101, "kept {remaining} frames");
+
+        let mut expected = floor_track();
+        expected.add_line(bump);
+        assert_eq!(
+            expected.entity_positions_at(100),
+            track.entity_positions_at(100)
+        );
+
+        track.remove_line(&bump);
+        assert_eq!(
+            floor_track().entity_positions_at(100),
+            track.entity_positions_at(100)
+        );
+    }
+
+    #[test]
+    fn spread_out_rider_edits_invalidate_frames() {
+        let skeleton: Skeleton = serde_json::from_str(
+            r#"{
+                "points": [
+                    { "index": "Custom(0)", "location": [0.0, 0.0] },
+                    { "index": "Custom(1)", "location": [50000.0, 0.0] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let mut track = Track::new(vec![Entity::from_skeleton(&skeleton).unwrap()], vec![]);
+        track.entity_positions_at(10);
+
+        // the rider covers too many cells to keep track of where it has been
+        track.add_line(
+            Line::builder()
+                .point(0.0, 5000.0)
+                .point(100.0, 5000.0)
+                .build(),
+        );
+
+        assert_eq!(1, track.precomputed_frame_count());
+    }
+
+    #[test]
+    fn cache_policies_match_full_cache() {
+        let expected = floor_track().entity_positions_at(150);
+
+        for policy in [
+            CachePolicy::Checkpoints { interval: 40 },
+            CachePolicy::Lru { capacity: 10 },
+        ] {
+            let mut track = floor_track();
+            track.set_cache_policy(policy);
+
+            assert_eq!(expected, track.entity_positions_at(150), "{policy:?}");
+            assert_eq!(
+                floor_track().entity_positions_at(75),
+                track.entity_positions_at(75),
+                "{policy:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn checkpoint_cache_is_bounded() {
+        let mut track = floor_track();
+        track.set_cache_policy(CachePolicy::Checkpoints { interval: 50 });
+        track.entity_positions_at(1000);
+
+        assert_eq!(1001, track.precomputed_frame_count());
+        assert_eq!(21, track.stored_frame_count());
+    }
+
+    #[test]
+    fn lru_cache_is_bounded() {
+        let mut track = floor_track();
+        track.set_cache_policy(CachePolicy::Lru { capacity: 5 });
+        track.entity_positions_at(1000);
+        track.entity_positions_at(3);
+
+        assert_eq!(6, track.stored_frame_count());
+    }
+
+    #[test]
+    fn sparse_cache_invalidates_near_edits() {
+        let bump = Line::builder().point(100.0, 10.0).point(120.0, 0.0).build();
+        let mut expected = floor_track();
+        expected.add_line(bump);
+
+        let mut track = floor_track();
+        track.set_cache_policy(CachePolicy::Checkpoints { interval: 30 });
+        track.entity_positions_at(100);
+        track.add_line(bump);
+
+        assert_eq!(
+            expected.entity_positions_at(100),
+            track.entity_positions_at(100)
+        );
+    }
+}
@@ -4,19 +4,23 @@ use serde::{Deserialize, Serialize};
 
 use crate::game::vector::Vector2D;
 
-#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug, Serialize, Deserialize)]
+/// Identifies a line in a track. A line keeps its id when other lines are added
+/// to or removed from the track.
+#[derive(
+    Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize, Default,
+)]
+pub struct LineId(pub u64);
+
+#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug, Serialize, Deserialize, Default)]
 pub enum LineType {
+    #[default]
     Normal,
-    Accelerate { amount: u64 },
+    Accelerate {
+        amount: u64,
+    },
     Scenery,
 }
 
-impl Default for LineType {
-    fn default() -> Self {
-        LineType::Normal
-    }
-}
-
 #[derive(Copy, Clone, Hash, PartialEq, Eq, Debug, Serialize, Deserialize, Default)]
 pub struct LinePoint {
     pub location: Vector2D,
@@ -130,6 +134,12 @@ impl Line {
         }
     }
 
+    /// Returns a copy of this line with the given extension ratio.
+    pub(crate) fn with_extension_ratio(mut self, extension_ratio: f64) -> Line {
+        self.extension_ratio = extension_ratio;
+        self
+    }
+
     pub fn as_vector2d(&self) -> Vector2D {
         self.ends.1.location - self.ends.0.location
     }
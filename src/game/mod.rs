@@ -1,9 +1,15 @@
+mod frame_cache;
 mod line;
+mod simulation;
 mod track;
+mod trigger;
 mod vector;
 
+pub use frame_cache::CachePolicy;
 pub use line::*;
+pub use simulation::{Simulation, MAX_SIMULATION_FRAME};
 pub use track::*;
+pub use trigger::{Trigger, TriggerAction, TriggerCondition};
 pub use vector::*;
 
 #[cfg(test)]
@@ -0,0 +1,278 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::rider::Entity;
+use crate::Track;
+
+/// The furthest frame a [`Simulation`] can be asked to reach, which is an hour of
+/// playback at Line Rider's 40 frames per second. Larger targets are lowered to
+/// this, so that the worker cannot fill memory with frames.
+pub const MAX_SIMULATION_FRAME: usize = 40 * 60 * 60;
+
+/// Simulates a [`Track`] on a background thread.
+///
+/// The simulation owns its track. Edit it with [`Simulation::edit`], which pauses the
+/// worker, forgets the frames that the edit affected and then lets the worker
+/// resimulate them, keeping every frame before the first affected one.
+///
+/// Dropping a simulation cancels it.
+pub struct Simulation {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    track: Track,
+    cancelled: AtomicBool,
+    work: Mutex<Work>,
+}
+
+struct Work {
+    /// The last frame the worker should simulate.
+    target: usize,
+    worker: Option<JoinHandle<()>>,
+    running: bool,
+}
+
+impl Simulation {
+    /// Starts simulating `track` up to the zero-indexed frame `until`.
+    pub fn start(track: Track, until: usize) -> Simulation {
+        let simulation = Simulation {
+            shared: Arc::new(Shared {
+                track,
+                cancelled: AtomicBool::new(false),
+                work: Mutex::new(Work {
+                    target: 0,
+                    worker: None,
+                    running: false,
+                }),
+            }),
+        };
+        simulation.simulate_until(until);
+
+        simulation
+    }
+
+    /// Gets the track being simulated.
+    pub fn track(&self) -> &Track {
+        &self.shared.track
+    }
+
+    /// Asks the worker to simulate up to the zero-indexed frame `frame`, for example
+    /// to stay ahead of a moving playhead. Does nothing if the simulation was cancelled
+    /// or is already simulating that far. Frames past [`MAX_SIMULATION_FRAME`] are
+    /// never simulated.
+    pub fn simulate_until(&self, frame: usize) {
+        let mut work = self.shared.work.lock().unwrap();
+        work.target = work.target.max(frame.min(MAX_SIMULATION_FRAME));
+
+        if work.running || self.is_cancelled() || self.progress() > work.target {
+            return;
+        }
+
+        work.running = true;
+        let shared = Arc::clone(&self.shared);
+        let previous = work.worker.replace(thread::spawn(move || shared.run()));
+        if let Some(previous) = previous {
+            // the previous worker has already given up its work, so this is quick
+            previous.join().unwrap();
+        }
+    }
+
+    /// Gets a frame if it has already been simulated and is kept in memory. Never
+    /// simulates anything on the calling thread, so which frames are available
+    /// depends on the track's [`CachePolicy`](crate::CachePolicy):
+    ///
+    ///  * `Full`: every frame the worker has simulated.
+    ///  * `Checkpoints`: only every `interval`th frame that the worker has simulated.
+    ///  * `Lru`: the first frame and the `capacity` frames the worker simulated
+    ///    most recently.
+    ///
+    /// Use [`Simulation::into_track`] to get the track back and simulate the other
+    /// frames on demand.
+    pub fn try_frame(&self, frame: usize) -> Option<Vec<Entity>> {
+        self.shared.track.stored_entity_positions_at(frame)
+    }
+
+    /// Returns how many frames have been simulated so far.
+    pub fn progress(&self) -> usize {
+        self.shared.track.precomputed_frame_count()
+    }
+
+    /// Returns the last frame the simulation has been asked to reach.
+    pub fn target(&self) -> usize {
+        self.shared.work.lock().unwrap().target
+    }
+
+    /// Returns whether every requested frame has been simulated.
+    pub fn is_finished(&self) -> bool {
+        self.progress() > self.target()
+    }
+
+    /// Stops the worker after the frame it is currently simulating.
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Edits the track, for example to add, move or remove lines, and returns what
+    /// `edit` returns. The worker is stopped during the edit. Afterwards it resumes
+    /// from the first frame that the edit affected, unless the simulation was
+    /// cancelled.
+    pub fn edit<R>(&mut self, edit: impl FnOnce(&mut Track) -> R) -> R {
+        let was_cancelled = self.is_cancelled();
+        self.stop_worker();
+
+        let shared = Arc::get_mut(&mut self.shared).expect("the worker was joined");
+        let result = edit(&mut shared.track);
+        *shared.cancelled.get_mut() = was_cancelled;
+
+        let target = self.target();
+        self.simulate_until(target);
+
+        result
+    }
+
+    /// Cancels the simulation and returns its track, including the frames that
+    /// were simulated.
+    pub fn into_track(self) -> Track {
+        self.stop_worker();
+
+        let shared = Arc::clone(&self.shared);
+        drop(self);
+
+        match Arc::try_unwrap(shared) {
+            Ok(shared) => shared.track,
+            Err(_) => unreachable!("the worker was joined"),
+        }
+    }
+
+    /// Cancels the simulation and waits for the worker to give up its work.
+    fn stop_worker(&self) {
+        self.cancel();
+        let worker = self.shared.work.lock().unwrap().worker.take();
+        if let Some(worker) = worker {
+            worker.join().unwrap();
+        }
+    }
+}
+
+impl Drop for Simulation {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl Shared {
+    fn run(&self) {
+        loop {
+            let target = {
+                let mut work = self.work.lock().unwrap();
+                let cancelled = self.cancelled.load(Ordering::Relaxed);
+                if cancelled || self.track.precomputed_frame_count() > work.target {
+                    work.running = false;
+                    return;
+                }
+
+                work.target
+            };
+
+            self.track
+                .precompute_until(target, || self.cancelled.load(Ordering::Relaxed));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::game::{CachePolicy, Line, Simulation, MAX_SIMULATION_FRAME};
+    use crate::test_tracks::floor_track;
+
+    fn wait_for(simulation: &Simulation) {
+        let start = Instant::now();
+        while !simulation.is_finished() {
+            assert!(start.elapsed() < Duration::from_secs(30), "simulation hung");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn background_simulation_matches_track() {
+        let simulation = Simulation::start(floor_track(), 300);
+        assert_eq!(None, simulation.try_frame(100_000));
+
+        wait_for(&simulation);
+        assert_eq!(
+            floor_track().entity_positions_at(300),
+            simulation.try_frame(300).unwrap()
+        );
+
+        simulation.simulate_until(400);
+        wait_for(&simulation);
+        assert_eq!(401, simulation.progress());
+    }
+
+    #[test]
+    fn background_simulation_only_returns_stored_frames() {
+        let mut track = floor_track();
+        track.set_cache_policy(CachePolicy::Checkpoints { interval: 50 });
+        let simulation = Simulation::start(track, 300);
+        wait_for(&simulation);
+
+        assert_eq!(None, simulation.try_frame(120));
+        assert_eq!(
+            floor_track().entity_positions_at(150),
+            simulation.try_frame(150).unwrap()
+        );
+        assert_eq!(301, simulation.progress());
+    }
+
+    #[test]
+    fn background_simulation_can_be_cancelled() {
+        let simulation = Simulation::start(floor_track(), usize::MAX - 1);
+        assert_eq!(MAX_SIMULATION_FRAME, simulation.target());
+        simulation.cancel();
+
+        let track = simulation.into_track();
+        assert!(track.precomputed_frame_count() < 100_000);
+    }
+
+    #[test]
+    fn edited_simulation_keeps_unaffected_frames() {
+        let far_away = Line::builder()
+            .point(5000.0, 5000.0)
+            .point(5100.0, 5000.0)
+            .build();
+        let wall = Line::builder()
+            .point(200.0, 10.0)
+            .point(200.0, -20.0)
+            .build();
+
+        let mut simulation = Simulation::start(floor_track(), 100);
+        wait_for(&simulation);
+
+        simulation.edit(|track| track.add_line(far_away));
+        assert!(simulation.try_frame(100).is_some());
+
+        simulation.edit(|track| track.add_line(wall));
+        assert!(simulation.try_frame(10).is_some());
+        wait_for(&simulation);
+
+        let mut expected = floor_track();
+        expected.add_line(far_away);
+        expected.add_line(wall);
+        assert_ne!(
+            floor_track().entity_positions_at(100),
+            expected.entity_positions_at(100)
+        );
+        assert_eq!(
+            expected.entity_positions_at(100),
+            simulation.try_frame(100).unwrap()
+        );
+    }
+}
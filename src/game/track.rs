@@ -1,19 +1,37 @@
-use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ops::Range;
 
-use physics::advance_frame::frame_after;
-
-use crate::game::line::Line;
+use crate::game::frame_cache::{CachePolicy, FrameCache};
+use crate::game::line::{Line, LineId};
+use crate::game::trigger::{LineHits, Trigger, TriggerCondition};
 use crate::game::vector::Vector2D;
-use crate::linestore::grid::Grid;
-use crate::rider::{Entity, EntityPoint};
-use crate::{physics, LineBuilder};
+use crate::linestore::grid::{Grid, GridVersion};
+use crate::physics::entity_physics::{DEFAULT_GRAVITY, DEFAULT_ITERATIONS};
+use crate::physics::events::Event;
+use crate::rider::{Entity, EntityId, EntityPoint, MountState};
+use crate::LineBuilder;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Settings which affect the physics of an entire track.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
 pub struct TrackMeta {
-    line_extension_ratio: f64,
-    gravity_well_height: f64,
-    remount: bool,
+    /// The ratio of a line's length that its extensions add to its hitbox.
+    pub line_extension_ratio: f64,
+    /// How far below a line a point can be while still being pushed back up.
+    pub gravity_well_height: f64,
+    /// Whether a bosh can get back on its sled after falling off.
+    pub remount: bool,
+    /// The grid revision used to find lines near a point. Use
+    /// [`Track::set_grid_version`] to change it on an existing track.
+    pub grid_version: GridVersion,
+    /// The acceleration applied to every point on every frame.
+    pub gravity: Vector2D,
+    /// How many times per frame bones and lines are applied to the riders. More
+    /// iterations make bones stiffer and lines harder to pass through.
+    pub iterations: u64,
+    /// Multiplies the friction of every point. 0 makes the track frictionless.
+    pub friction_scale: f64,
 }
 
 impl Default for TrackMeta {
@@ -22,18 +40,31 @@ impl Default for TrackMeta {
             line_extension_ratio: 0.25,
             gravity_well_height: 10.0,
             remount: false,
+            grid_version: GridVersion::V6_2,
+            gravity: DEFAULT_GRAVITY,
+            iterations: DEFAULT_ITERATIONS,
+            friction_scale: 1.0,
         }
     }
 }
 
 /// A track in linerider.
+///
+/// Tracks can be serialized with serde, see [`TRACK_FORMAT_VERSION`](crate::formats::TRACK_FORMAT_VERSION)
+/// for details.
+///
+/// Frames are simulated lazily when they are first requested. A track can be
+/// shared between threads, which may all request frames at the same time.
 #[derive(Debug)]
 pub struct Track {
+    /// Changing physics settings through this field does not clear frames which
+    /// were already simulated, use [`Track::set_meta`] instead.
     pub meta: TrackMeta,
 
     grid: Grid,
+    triggers: Vec<Trigger>,
 
-    precomputed_rider_positions: RefCell<Vec<Vec<Entity>>>,
+    frame_cache: FrameCache,
 }
 
 impl Track {
@@ -41,7 +72,8 @@ impl Track {
         Track {
             meta: Default::default(),
             grid: Grid::new(lines),
-            precomputed_rider_positions: RefCell::new(vec![starting_positions]),
+            triggers: vec![],
+            frame_cache: FrameCache::new(with_unique_ids(starting_positions)),
         }
     }
     pub fn new_with_meta(
@@ -50,9 +82,73 @@ impl Track {
         meta: TrackMeta,
     ) -> Track {
         Track {
+            grid: Grid::new_with_version(lines, meta.grid_version),
             meta,
-            grid: Grid::new(lines),
-            precomputed_rider_positions: RefCell::new(vec![starting_positions]),
+            triggers: vec![],
+            frame_cache: FrameCache::new(with_unique_ids(starting_positions)),
+        }
+    }
+
+    /// Replaces the settings of the track, re-simulating every frame.
+    pub fn set_meta(&mut self, meta: TrackMeta) {
+        if meta.grid_version != self.meta.grid_version {
+            self.grid.set_version(meta.grid_version);
+        }
+        self.meta = meta;
+        self.frame_cache.invalidate_from(1);
+    }
+
+    /// Changes the grid revision of the track, re-registering every line.
+    pub fn set_grid_version(&mut self, version: GridVersion) {
+        self.meta.grid_version = version;
+        self.grid.set_version(version);
+        self.frame_cache.invalidate_from(1);
+    }
+
+    /// Gets the policy deciding which simulated frames are kept in memory.
+    pub fn cache_policy(&self) -> CachePolicy {
+        self.frame_cache.policy()
+    }
+
+    /// Changes which simulated frames are kept in memory. Frames that the new
+    /// policy would not keep are dropped.
+    pub fn set_cache_policy(&mut self, policy: CachePolicy) {
+        self.frame_cache.set_policy(policy);
+    }
+
+    /// Gets the triggers of the track, in the order they were added.
+    pub fn triggers(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
+    /// Adds a trigger to the track. Triggers which take effect on the same frame are
+    /// applied in the order they were added.
+    pub fn add_trigger(&mut self, trigger: Trigger) {
+        self.invalidate_frames_after_trigger(&trigger);
+        self.triggers.push(trigger);
+    }
+
+    /// Removes the trigger at `index` from the track, returning it.
+    pub fn remove_trigger(&mut self, index: usize) -> Option<Trigger> {
+        if index >= self.triggers.len() {
+            return None;
+        }
+        let trigger = self.triggers.remove(index);
+        self.invalidate_frames_after_trigger(&trigger);
+
+        Some(trigger)
+    }
+
+    /// Removes cached frames that `trigger` may have affected.
+    fn invalidate_frames_after_trigger(&mut self, trigger: &Trigger) {
+        match trigger.condition {
+            TriggerCondition::Frame(frame) => self.frame_cache.invalidate_from(frame),
+            // a line can only be hit once a rider is near it
+            TriggerCondition::LineHit(id) => {
+                if let Some(&line) = self.line(id) {
+                    self.invalidate_frames_near(&[line]);
+                }
+            }
         }
     }
 
@@ -65,60 +161,209 @@ impl Track {
         self.grid.all_lines()
     }
 
-    /// Adds a line to the track.
-    pub fn add_line(&mut self, line: Line) {
-        self.grid.add_line(line);
-        self.precomputed_rider_positions.borrow_mut().drain(1..);
+    /// Gets all lines in the track along with their ids.
+    pub fn lines_with_ids(&self) -> impl Iterator<Item = (LineId, &Line)> {
+        self.grid
+            .all_ids()
+            .iter()
+            .copied()
+            .zip(self.grid.all_lines())
+    }
+
+    /// Gets the line with the given id.
+    pub fn line(&self, id: LineId) -> Option<&Line> {
+        self.grid.line(id)
+    }
+
+    /// Adds a line to the track, returning its id.
+    pub fn add_line(&mut self, line: Line) -> LineId {
+        let id = self.grid.add_line(line);
+        self.invalidate_frames_near(&[line]);
+
+        id
+    }
+
+    /// Adds a line to the track with a specific id. Returns None if a line
+    /// with that id already exists.
+    pub fn add_line_with_id(&mut self, id: LineId, line: Line) -> Option<()> {
+        self.grid.add_line_with_id(id, line)?;
+        self.invalidate_frames_near(&[line]);
+
+        Some(())
     }
 
     /// Removes a single line from the track.
     pub fn remove_line(&mut self, line: &Line) {
-        self.grid.remove_line(line);
-        self.precomputed_rider_positions.borrow_mut().drain(1..);
+        if self.grid.remove_line(line).is_some() {
+            self.invalidate_frames_near(&[*line]);
+        }
+    }
+
+    /// Removes the line with the given id from the track, returning it.
+    pub fn remove_line_by_id(&mut self, id: LineId) -> Option<Line> {
+        let line = self.grid.remove_line_by_id(id)?;
+        self.invalidate_frames_near(&[line]);
+
+        Some(line)
     }
 
-    /// Gets all of the lines near a point.
+    /// Replaces the line with the given id, returning the previous line.
+    pub fn update_line(&mut self, id: LineId, line: Line) -> Option<Line> {
+        let old_line = self.grid.update_line(id, line)?;
+        self.invalidate_frames_near(&[old_line, line]);
+
+        Some(old_line)
+    }
+
+    /// Removes cached frames starting from the first frame in which a rider came
+    /// near one of `lines`, since earlier frames cannot have been affected by them.
+    fn invalidate_frames_near(&mut self, lines: &[Line]) {
+        // one cell for the cells searched by `lines_near`, and one more for
+        // how far a point may move while a frame is being computed
+        let regions: Vec<_> = lines
+            .iter()
+            .map(|line| self.grid.region_around(line, 2))
+            .collect();
+        self.frame_cache.invalidate_near(&regions);
+    }
+
+    /// Returns the number of frames which have been simulated since the last edit
+    /// that affected them.
+    pub fn precomputed_frame_count(&self) -> usize {
+        self.frame_cache.simulated_len()
+    }
+
+    /// Returns the number of frames currently kept in memory, see [`CachePolicy`].
+    pub fn stored_frame_count(&self) -> usize {
+        self.frame_cache.stored_len()
+    }
+
+    /// Gets all of the lines near a point, ordered by descending id.
     pub fn lines_near(&self, point: Vector2D) -> Vec<&Line> {
         self.grid.lines_near(point, 1)
     }
 
-    /// Gets all of the lines in a rectangle.
+    /// Gets all of the lines near a point along with their ids, ordered by descending id.
+    pub fn lines_near_with_ids(&self, point: Vector2D) -> Vec<(LineId, &Line)> {
+        self.grid.lines_near_with_ids(point, 1)
+    }
+
+    /// Gets all of the lines in a rectangle, ordered by descending id.
     pub fn lines_near_box(&self, p1: Vector2D, p2: Vector2D) -> Vec<&Line> {
         self.grid.lines_near_box(p1, p2)
     }
 
     /// Gets the rider positions for a zero-indexed frame.
     pub fn entity_positions_at(&self, frame: usize) -> Vec<Entity> {
-        let mut position_cache = self.precomputed_rider_positions.borrow_mut();
-        if let Some(riders) = position_cache.get(frame) {
-            riders.clone()
-        } else {
-            let len = position_cache.len();
-            for _ in len..=frame {
-                let next_positions = frame_after(position_cache.last().unwrap(), self);
-                position_cache.push(next_positions);
-            }
+        self.frame_cache.get(frame, self)
+    }
+
+    /// Gets the entities at a zero-indexed frame if that frame is kept in memory,
+    /// without simulating anything. See [`CachePolicy`] for which frames are kept.
+    pub fn stored_entity_positions_at(&self, frame: usize) -> Option<Vec<Entity>> {
+        self.frame_cache.get_stored(frame)
+    }
+
+    /// Iterates over every frame of the track, starting at the first.
+    ///
+    /// See [`Track::frames_from`].
+    pub fn frames(&self) -> Frames<'_> {
+        self.frames_from(0)
+    }
 
-            position_cache.last().unwrap().clone()
+    /// Iterates over every frame of the track, starting at a zero-indexed frame.
+    ///
+    /// Frames are simulated as the iterator advances and handed out without being
+    /// cloned or added to the track's cache, which makes this cheaper than calling
+    /// [`Track::entity_positions_at`] for every frame when walking through a track.
+    /// The iterator never ends.
+    pub fn frames_from(&self, frame: usize) -> Frames<'_> {
+        Frames {
+            track: self,
+            next: self.entity_positions_at(frame),
+            next_frame: frame,
+            line_hits: self.frame_cache.line_hits(),
         }
     }
 
-    /// Adds a new rider to the track.
-    pub fn create_entity(&mut self, entity: Entity) {
-        let position_cache = self.precomputed_rider_positions.get_mut();
-        let initial_frame = position_cache.get_mut(0).unwrap();
+    /// Gets the events that happened while simulating a zero-indexed frame.
+    pub fn events_at(&self, frame: usize) -> Vec<Event> {
+        self.events_in(frame..frame + 1)
+    }
+
+    /// Gets the events that happened while simulating a range of zero-indexed frames,
+    /// in order of frame.
+    ///
+    /// Events are not cached, so the frames are simulated again, starting from the
+    /// frame before the range.
+    pub fn events_in(&self, frames: Range<usize>) -> Vec<Event> {
+        let start = frames.start.max(1);
+        if start >= frames.end {
+            return vec![];
+        }
+
+        let mut riders = self.entity_positions_at(start - 1);
+        let mut line_hits = self.frame_cache.line_hits();
+        let mut events = vec![];
+        for frame in start..frames.end {
+            riders = self.step(&riders, frame, &mut line_hits, Some(&mut events));
+        }
+
+        events
+    }
+
+    /// Finds the first frame in which a bosh fell off its sled, searching up to and
+    /// including the zero-indexed frame `until`. This is the first frame with an
+    /// [`Event::Dismount`].
+    ///
+    /// Uses the frames kept in memory, and only re-simulates the frames that the
+    /// track's [`CachePolicy`] did not keep.
+    pub fn first_crash_frame(&self, until: usize) -> Option<usize> {
+        self.entity_positions_at(until);
+
+        let mut line_hits = self.frame_cache.line_hits();
+        let mut previous = self.entity_positions_at(0);
+        for frame in 1..=until {
+            let riders = self
+                .stored_entity_positions_at(frame)
+                .unwrap_or_else(|| self.step(&previous, frame, &mut line_hits, None));
+            if previous.iter().any(|entity| fell_off(entity, &riders)) {
+                return Some(frame);
+            }
+            previous = riders;
+        }
+
+        None
+    }
+
+    /// Simulates up to a zero-indexed frame, stopping early if `should_stop`
+    /// returns true. Returns whether the frame was reached.
+    pub(crate) fn precompute_until(&self, frame: usize, should_stop: impl Fn() -> bool) -> bool {
+        self.frame_cache
+            .simulate(frame, self, should_stop)
+            .is_some()
+    }
+
+    /// Adds a new rider to the track. If another rider already has the same id, the
+    /// new rider is given an unused id.
+    pub fn create_entity(&mut self, mut entity: Entity) {
+        let frame_cache = &mut self.frame_cache;
+        let initial_frame = frame_cache.first_frame_mut();
+        if initial_frame.iter().any(|e| e.id == entity.id) {
+            entity.id = EntityId::unused(&*initial_frame);
+        }
         initial_frame.push(entity);
 
-        position_cache.drain(1..);
+        frame_cache.invalidate_from(1);
     }
 
     /// Removes a rider from the track.
     pub fn remove_entity(&mut self, entity: Entity) -> Option<()> {
-        let position_cache = self.precomputed_rider_positions.get_mut();
-        let initial_frame = position_cache.get_mut(0).unwrap();
+        let frame_cache = &mut self.frame_cache;
+        let initial_frame = frame_cache.first_frame_mut();
         initial_frame.remove(initial_frame.iter().position(|e| *e == entity)?);
 
-        position_cache.drain(1..);
+        frame_cache.invalidate_from(1);
         Some(())
     }
 
@@ -175,12 +420,76 @@ impl Track {
     }
 }
 
+/// Gives every entity which has the same id as an earlier entity an unused id.
+fn with_unique_ids(mut entities: Vec<Entity>) -> Vec<Entity> {
+    let mut seen = HashSet::new();
+    for i in 0..entities.len() {
+        if !seen.insert(entities[i].id) {
+            entities[i].id = EntityId::unused(&entities);
+            seen.insert(entities[i].id);
+        }
+    }
+
+    entities
+}
+
+/// Returns whether `entity` broke apart or its bosh came off its sled on the way to
+/// the frame `next`.
+fn fell_off(entity: &Entity, next: &[Entity]) -> bool {
+    let riding = |state| matches!(state, MountState::Mounted | MountState::Remounting(_));
+
+    match next.iter().find(|e| e.id == entity.id) {
+        Some(next) => riding(entity.mount_state) && !riding(next.mount_state),
+        None => true,
+    }
+}
+
+/// An iterator over the frames of a [`Track`], created by [`Track::frames`] and
+/// [`Track::frames_from`].
+pub struct Frames<'t> {
+    track: &'t Track,
+    next: Vec<Entity>,
+    next_frame: usize,
+    line_hits: LineHits,
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Vec<Entity>;
+
+    fn next(&mut self) -> Option<Vec<Entity>> {
+        // the following frame is simulated now so that this one can be moved out
+        self.next_frame += 1;
+        let following = self
+            .track
+            .step(&self.next, self.next_frame, &mut self.line_hits, None);
+
+        Some(std::mem::replace(&mut self.next, following))
+    }
+}
+
 impl Clone for Track {
     fn clone(&self) -> Self {
         Track {
             meta: self.meta.clone(),
             grid: self.grid.clone(),
-            precomputed_rider_positions: self.precomputed_rider_positions.clone(),
+            triggers: self.triggers.clone(),
+            frame_cache: self.frame_cache.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_tracks::floor_track;
+
+    #[test]
+    fn frames_match_entity_positions() {
+        let track = floor_track();
+
+        for (frame, riders) in track.frames_from(5).take(20).enumerate() {
+            assert_eq!(floor_track().entity_positions_at(frame + 5), riders);
         }
+        assert_eq!(track.entity_positions_at(0), track.frames().next().unwrap());
+        assert_eq!(6, track.precomputed_frame_count());
     }
 }
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::{LineId, LineType, Track, Vector2D};
+use crate::physics::advance_frame::frame_after_with_settings;
+use crate::physics::events::Event;
+use crate::physics::settings::FrameSettings;
+use crate::rider::Entity;
+
+/// Changes the physics of a track partway through its simulation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Trigger {
+    pub condition: TriggerCondition,
+    pub action: TriggerAction,
+}
+
+/// When a [`Trigger`] fires.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum TriggerCondition {
+    /// Fires on a zero-indexed frame, changing how that frame is simulated.
+    Frame(usize),
+    /// Fires the first time any rider touches a line, changing how the frames after
+    /// the touch are simulated.
+    LineHit(LineId),
+}
+
+/// What a [`Trigger`] changes. Changes last until another trigger changes the same
+/// setting.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum TriggerAction {
+    SetGravity(Vector2D),
+    SetIterations(u64),
+    SetFrictionScale(f64),
+    /// Makes a line behave as if it had a different type.
+    #[serde(rename_all = "camelCase")]
+    SetLineType {
+        line: LineId,
+        line_type: LineType,
+    },
+}
+
+impl TriggerAction {
+    fn apply(self, settings: &mut FrameSettings) {
+        match self {
+            TriggerAction::SetGravity(gravity) => settings.gravity = gravity,
+            TriggerAction::SetIterations(iterations) => settings.iterations = iterations,
+            TriggerAction::SetFrictionScale(scale) => settings.friction_scale = scale,
+            TriggerAction::SetLineType { line, line_type } => {
+                settings.line_types.insert(line, line_type);
+            }
+        }
+    }
+}
+
+/// The first frame in which each line with a [`TriggerCondition::LineHit`] trigger
+/// was touched.
+pub(crate) type LineHits = HashMap<LineId, usize>;
+
+impl Track {
+    /// Gets the settings used to simulate a zero-indexed frame, given the lines which
+    /// have been hit so far.
+    pub(crate) fn settings_at(&self, frame: usize, line_hits: &LineHits) -> FrameSettings {
+        let mut settings = FrameSettings::from_meta(&self.meta);
+
+        let mut fired: Vec<_> = self
+            .triggers()
+            .iter()
+            .filter_map(|trigger| {
+                let takes_effect = match trigger.condition {
+                    TriggerCondition::Frame(n) => Some(n),
+                    TriggerCondition::LineHit(line) => line_hits.get(&line).map(|hit| hit + 1),
+                };
+                takes_effect
+                    .filter(|&f| f <= frame)
+                    .map(|f| (f, trigger.action))
+            })
+            .collect();
+        // triggers which take effect on the same frame are applied in the order they
+        // were added
+        fired.sort_by_key(|&(f, _)| f);
+
+        for (_, action) in fired {
+            action.apply(&mut settings);
+        }
+
+        settings
+    }
+
+    /// Simulates the frame after `riders`, which is the zero-indexed frame `frame`,
+    /// with the triggers that fired before it. Lines with triggers that were hit
+    /// for the first time are added to `line_hits`.
+    pub(crate) fn step(
+        &self,
+        riders: &[Entity],
+        frame: usize,
+        line_hits: &mut LineHits,
+        events: Option<&mut Vec<Event>>,
+    ) -> Vec<Entity> {
+        let settings = self.settings_at(frame, line_hits);
+        let unhit_lines: Vec<LineId> = self
+            .triggers()
+            .iter()
+            .filter_map(|trigger| match trigger.condition {
+                TriggerCondition::LineHit(line) if !line_hits.contains_key(&line) => Some(line),
+                _ => None,
+            })
+            .collect();
+
+        if unhit_lines.is_empty() {
+            return frame_after_with_settings(riders, self, &settings, frame, events);
+        }
+
+        // contacts are needed to know which lines were hit
+        let mut own_events = vec![];
+        let events = events.unwrap_or(&mut own_events);
+        let first_new_event = events.len();
+        let next = frame_after_with_settings(riders, self, &settings, frame, Some(events));
+
+        for event in &events[first_new_event..] {
+            if let Event::Contact { line, .. } = event {
+                if unhit_lines.contains(line) {
+                    line_hits.entry(*line).or_insert(frame);
+                }
+            }
+        }
+
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{
+        LineId, LineType, Track, Trigger, TriggerAction, TriggerCondition, Vector2D,
+    };
+    use crate::rider::{Entity, PointIndex};
+    use crate::test_tracks::{avg_velocity, floor_track};
+
+    fn with_trigger(mut track: Track, condition: TriggerCondition, action: TriggerAction) -> Track {
+        track.add_trigger(Trigger { condition, action });
+        track
+    }
+
+    #[test]
+    fn frame_trigger_changes_gravity() {
+        let track = with_trigger(
+            Track::new(vec![Entity::default_boshsled()], vec![]),
+            TriggerCondition::Frame(10),
+            TriggerAction::SetGravity(Vector2D(0.0, 0.0)),
+        );
+        let plain = Track::new(vec![Entity::default_boshsled()], vec![]);
+
+        assert_eq!(plain.entity_positions_at(9), track.entity_positions_at(9));
+        assert_ne!(plain.entity_positions_at(10), track.entity_positions_at(10));
+        let change = avg_velocity(&track.entity_positions_at(40)[0])
+            - avg_velocity(&track.entity_positions_at(10)[0]);
+        assert!(change.length_squared() < 1e-18, "{change:?}");
+    }
+
+    #[test]
+    fn line_hit_trigger_fires_after_contact() {
+        let track = with_trigger(
+            floor_track(),
+            TriggerCondition::LineHit(LineId(0)),
+            TriggerAction::SetGravity(Vector2D(0.0, -0.5)),
+        );
+        let hit = floor_track().events_in(0..100)[0].frame();
+
+        assert_eq!(
+            floor_track().entity_positions_at(hit),
+            track.entity_positions_at(hit)
+        );
+        assert_ne!(
+            floor_track().entity_positions_at(hit + 1),
+            track.entity_positions_at(hit + 1)
+        );
+    }
+
+    #[test]
+    fn line_type_trigger_makes_line_scenery() {
+        let track = with_trigger(
+            floor_track(),
+            TriggerCondition::Frame(30),
+            TriggerAction::SetLineType {
+                line: LineId(0),
+                line_type: LineType::Scenery,
+            },
+        );
+
+        let tail = |frame| {
+            track.entity_positions_at(frame)[0]
+                .point_at(PointIndex::SledTail)
+                .location
+        };
+        assert!(tail(29).1 <= 10.0);
+        assert!(tail(60).1 > 20.0);
+    }
+
+    #[test]
+    fn editing_triggers_invalidates_frames() {
+        let condition = TriggerCondition::Frame(50);
+        let action = TriggerAction::SetIterations(1);
+
+        let mut track = floor_track();
+        track.entity_positions_at(100);
+        track.add_trigger(Trigger { condition, action });
+        assert_eq!(50, track.precomputed_frame_count());
+        assert_eq!(
+            with_trigger(floor_track(), condition, action).entity_positions_at(100),
+            track.entity_positions_at(100)
+        );
+
+        track.remove_trigger(0).unwrap();
+        assert_eq!(
+            floor_track().entity_positions_at(100),
+            track.entity_positions_at(100)
+        );
+
+        track.add_trigger(Trigger {
+            condition: TriggerCondition::LineHit(LineId(0)),
+            action,
+        });
+        assert_eq!(
+            with_trigger(floor_track(), TriggerCondition::LineHit(LineId(0)), action)
+                .entity_positions_at(100),
+            track.entity_positions_at(100)
+        );
+    }
+
+    #[test]
+    fn triggers_apply_to_frames_and_events() {
+        let track = with_trigger(
+            floor_track(),
+            TriggerCondition::LineHit(LineId(0)),
+            TriggerAction::SetGravity(Vector2D(0.1, -0.3)),
+        );
+        let expected = with_trigger(
+            floor_track(),
+            TriggerCondition::LineHit(LineId(0)),
+            TriggerAction::SetGravity(Vector2D(0.1, -0.3)),
+        );
+
+        for (frame, riders) in track.frames().take(60).enumerate() {
+            assert_eq!(expected.entity_positions_at(frame), riders);
+        }
+        let last_contact = track.events_in(0..60).last().unwrap().frame();
+        assert!(last_contact < 59, "rider should leave the floor");
+    }
+}
@@ -1,11 +1,11 @@
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
-use std::io::Read;
+use std::io::{Read, Write};
 use std::iter::Sum;
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
 use anyhow::{Context, Error};
-use read_from::{LittleEndian, ReadFrom};
+use read_from::{LittleEndian, ReadFrom, WriteTo};
 use serde::{Deserialize, Serialize};
 
 /// Represents a 2-dimensional vector of floats. Typically used to represent a point
@@ -55,19 +55,25 @@ impl Vector2D {
         Vector2D(-self.1, self.0)
     }
 
+    /// Returns true if neither coordinate is infinite or NaN.
+    pub fn is_finite(self) -> bool {
+        self.0.is_finite() && self.1.is_finite()
+    }
+
     /// mirrors the vector over the y=x line.
     pub fn mirror_over_yx(self) -> Vector2D {
         Vector2D(self.1, self.0)
     }
 
-    /// Rotates the vector by some arbitrary number of radians
+    /// Rotates the vector by some arbitrary number of radians. Since y points down,
+    /// positive angles rotate clockwise on screen.
     pub fn rotate_rad(self, radians: f64) -> Vector2D {
         let sin_angle = f64::sin(radians);
         let cos_angle = f64::cos(radians);
 
         Vector2D(
             self.0 * cos_angle - self.1 * sin_angle,
-            self.0 * sin_angle - self.1 * cos_angle,
+            self.0 * sin_angle + self.1 * cos_angle,
         )
     }
 
@@ -204,3 +210,16 @@ impl ReadFrom for Vector2D {
         ))
     }
 }
+
+impl WriteTo for Vector2D {
+    type Error = Error;
+
+    fn write_to<W: Write>(&self, mut output: W) -> Result<usize, Self::Error> {
+        Ok(LittleEndian(self.0)
+            .write_to(&mut output)
+            .context("error in Vector2D while writing x")?
+            + LittleEndian(self.1)
+                .write_to(&mut output)
+                .context("error in Vector2D while writing y")?)
+    }
+}
@@ -1,11 +1,15 @@
 extern crate core;
 
+pub mod formats;
 mod game;
 mod linestore;
 pub mod physics;
 pub mod rider;
+#[cfg(test)]
+mod test_tracks;
 
 pub use game::*;
+pub use linestore::grid::GridVersion;
 
 #[cfg(test)]
 mod tests {
@@ -1,23 +1,51 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
-use crate::game::Line;
 use crate::game::Vector2D;
-use crate::linestore::raw_store::{RawStore, RemoveLineResult, StoreIndex};
+use crate::game::{Line, LineId};
+use crate::linestore::raw_store::RawStore;
+use serde::{Deserialize, Serialize};
 
 const CELL_SIZE: i64 = 20;
 
+/// The revision of Line Rider's grid that a track was made for. Each revision
+/// registers lines into grid cells differently, which changes which lines
+/// a point can collide with.
+///
+/// Only the 6.1 and 6.2 grids are implemented. Tracks made for the 6.0 grid are
+/// rejected when loaded, since simulating them with another grid would give
+/// different results.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum GridVersion {
+    /// Steps from cell to cell by overshooting cell borders when moving left or
+    /// up, which may skip cells that a line only clips the corner of.
+    #[serde(rename = "6.1")]
+    V6_1,
+    /// Registers a line in every cell that it passes through.
+    #[default]
+    #[serde(rename = "6.2")]
+    V6_2,
+}
+
 /// Data structure used to query lines nearby the rider in
 /// an efficient manner.
 #[derive(Eq, PartialEq, Clone, Default, Debug)]
 pub struct Grid {
     lines: RawStore,
+    version: GridVersion,
 
-    grid: HashMap<GridIndex, Vec<StoreIndex>>,
+    grid: HashMap<GridIndex, Vec<LineId>>,
 }
 
 impl Grid {
     pub fn new(lines: Vec<Line>) -> Grid {
-        let mut grid: Grid = Default::default();
+        Grid::new_with_version(lines, Default::default())
+    }
+
+    pub fn new_with_version(lines: Vec<Line>, version: GridVersion) -> Grid {
+        let mut grid = Grid {
+            version,
+            ..Default::default()
+        };
         for line in lines {
             grid.add_line(line);
         }
@@ -25,75 +53,142 @@ impl Grid {
         grid
     }
 
+    /// Re-registers all lines using a different grid version.
+    pub fn set_version(&mut self, version: GridVersion) {
+        self.version = version;
+        self.grid.clear();
+
+        let lines: Vec<_> = self
+            .lines
+            .all_ids()
+            .iter()
+            .copied()
+            .zip(self.lines.all_lines().clone())
+            .collect();
+        for (id, line) in lines {
+            self.register_line(&line, id);
+        }
+    }
+
     pub fn all_lines(&self) -> &Vec<Line> {
         self.lines.all_lines()
     }
 
+    /// Returns the ids of all lines, in the same order as [`Grid::all_lines`].
+    pub fn all_ids(&self) -> &Vec<LineId> {
+        self.lines.all_ids()
+    }
+
+    pub fn line(&self, id: LineId) -> Option<&Line> {
+        self.lines.line(id)
+    }
+
+    /// Returns the lines in the cells around `loc`, ordered by descending id. This is
+    /// the order in which Line Rider applies lines to a point.
     pub fn lines_near(&self, loc: Vector2D, grid_radius: u8) -> Vec<&Line> {
-        self.nearby_line_indices(loc, grid_radius)
+        self.lines_near_with_ids(loc, grid_radius)
             .into_iter()
-            .map(|l| self.lines.line_at(l).expect("no line at index"))
+            .map(|(_, line)| line)
             .collect()
     }
 
+    /// Like [`Grid::lines_near`], but also returns the id of each line.
+    pub fn lines_near_with_ids(&self, loc: Vector2D, grid_radius: u8) -> Vec<(LineId, &Line)> {
+        self.nearby_line_ids(loc, grid_radius)
+            .into_iter()
+            .rev()
+            .map(|id| (id, self.lines.line(id).expect("no line with id")))
+            .collect()
+    }
+
+    /// Returns the lines in the cells covered by a rectangle, ordered by descending id.
     pub fn lines_near_box(&self, loc1: Vector2D, loc2: Vector2D) -> Vec<&Line> {
-        self.line_indices_in_rectangle(loc1, loc2)
+        self.line_ids_in_rectangle(loc1, loc2)
             .into_iter()
-            .map(|l| self.lines.line_at(l).expect("no line at index"))
+            .rev()
+            .map(|id| self.lines.line(id).expect("no line with id"))
             .collect()
     }
 
-    pub fn add_line(&mut self, line: Line) {
-        let lines_idx = self.lines.add_line(line);
+    /// Adds a line, returning its newly assigned id.
+    pub fn add_line(&mut self, line: Line) -> LineId {
+        let id = self.lines.add_line(line);
+        self.register_line(&line, id);
 
-        for index in GridIndex::iter_over_line(&line) {
-            self.grid.entry(index).or_default().push(lines_idx);
-        }
+        id
     }
 
-    pub fn remove_line(&mut self, line: &Line) {
-        // remove lines_idx from grid
-        let result = self.lines.remove_line(line);
+    /// Adds a line with a specific id. Returns None if the id is already taken.
+    pub fn add_line_with_id(&mut self, id: LineId, line: Line) -> Option<()> {
+        self.lines.add_line_with_id(id, line)?;
+        self.register_line(&line, id);
 
-        match result {
-            RemoveLineResult::NoneRemoved => {}
-            RemoveLineResult::RemovedNoSwap(idx) => {
-                self.remove_line_for_real(line, idx);
-            }
-            RemoveLineResult::RemovedAndNeedsSwap {
-                from: from_idx,
-                to: to_idx,
-            } => {
-                self.remove_line_for_real(line, to_idx);
-
-                // replace instances of line
-                if let Some(line) = self.lines.line_at(to_idx) {
-                    for grid_idx in GridIndex::iter_over_line(line) {
-                        if let Some(idxs) = self.grid.get_mut(&grid_idx) {
-                            idxs.iter_mut().for_each(|idx| {
-                                if *idx == from_idx {
-                                    *idx = to_idx
-                                }
-                            })
-                        }
-                    }
+        Some(())
+    }
+
+    /// Removes a line equal to `line`, returning the id it had.
+    pub fn remove_line(&mut self, line: &Line) -> Option<LineId> {
+        let id = self.lines.id_of(line)?;
+        self.remove_line_by_id(id);
+
+        Some(id)
+    }
+
+    pub fn remove_line_by_id(&mut self, id: LineId) -> Option<Line> {
+        let line = self.lines.remove_line(id)?;
+        self.unregister_line(&line, id);
+
+        Some(line)
+    }
+
+    /// Replaces the line with the given id, returning the previous line.
+    pub fn update_line(&mut self, id: LineId, line: Line) -> Option<Line> {
+        let old_line = self.lines.update_line(id, line)?;
+        self.unregister_line(&old_line, id);
+        self.register_line(&line, id);
+
+        Some(old_line)
+    }
+
+    /// Returns the cells within `grid_radius` cells of the cells that `line` is
+    /// registered in.
+    pub fn region_around(&self, line: &Line, grid_radius: u8) -> GridRegion {
+        let grid_radius = grid_radius as i64;
+        let mut cells = HashSet::new();
+
+        for index in GridIndex::iter_over_line(line, self.version) {
+            for dx in -grid_radius..=grid_radius {
+                for dy in -grid_radius..=grid_radius {
+                    cells.insert(GridIndex(index.0 + dx, index.1 + dy));
                 }
             }
         }
+
+        GridRegion(cells)
     }
 
-    fn remove_line_for_real(&mut self, line: &Line, replaced_idx: StoreIndex) {
-        for grid_idx in GridIndex::iter_over_line(line) {
-            if let Some(idxs) = self.grid.get_mut(&grid_idx) {
-                if let Some(idx_pos) = idxs.iter().position(|idx| *idx == replaced_idx) {
-                    idxs.swap_remove(idx_pos);
+    fn register_line(&mut self, line: &Line, id: LineId) {
+        for index in GridIndex::iter_over_line(line, self.version) {
+            let ids = self.grid.entry(index).or_default();
+            // the classic iterator may yield the same cell twice in a row
+            if ids.last() != Some(&id) {
+                ids.push(id);
+            }
+        }
+    }
+
+    fn unregister_line(&mut self, line: &Line, id: LineId) {
+        for grid_idx in GridIndex::iter_over_line(line, self.version) {
+            if let Some(ids) = self.grid.get_mut(&grid_idx) {
+                if let Some(id_pos) = ids.iter().position(|each_id| *each_id == id) {
+                    ids.swap_remove(id_pos);
                 }
             }
         }
     }
 
-    fn nearby_line_indices(&self, loc: Vector2D, grid_radius: u8) -> HashSet<StoreIndex> {
-        let mut nearby_line_indices: HashSet<StoreIndex> = Default::default();
+    fn nearby_line_ids(&self, loc: Vector2D, grid_radius: u8) -> BTreeSet<LineId> {
+        let mut nearby_line_ids: BTreeSet<LineId> = Default::default();
 
         let center = GridIndex::from_location(loc);
 
@@ -105,19 +200,17 @@ impl Grid {
                 grid_index.0 += dx;
                 grid_index.1 += dy;
 
-                if let Some(store_indices) = self.grid.get(&grid_index).cloned() {
-                    for store_index in store_indices {
-                        nearby_line_indices.insert(store_index);
-                    }
+                if let Some(ids) = self.grid.get(&grid_index) {
+                    nearby_line_ids.extend(ids);
                 }
             }
         }
 
-        nearby_line_indices
+        nearby_line_ids
     }
 
-    fn line_indices_in_rectangle(&self, loc1: Vector2D, loc2: Vector2D) -> HashSet<StoreIndex> {
-        let mut nearby_line_indices: HashSet<StoreIndex> = Default::default();
+    fn line_ids_in_rectangle(&self, loc1: Vector2D, loc2: Vector2D) -> BTreeSet<LineId> {
+        let mut nearby_line_ids: BTreeSet<LineId> = Default::default();
 
         let idx1 = GridIndex::from_location(loc1);
         let idx2 = GridIndex::from_location(loc2);
@@ -125,22 +218,49 @@ impl Grid {
         for x in i64::min(idx1.0, idx2.0)..=i64::max(idx1.0, idx2.0) {
             for y in i64::min(idx1.1, idx2.1)..=i64::max(idx1.1, idx2.1) {
                 let grid_index = GridIndex(x, y);
-                if let Some(store_indices) = self.grid.get(&grid_index).cloned() {
-                    for store_index in store_indices {
-                        nearby_line_indices.insert(store_index);
-                    }
+                if let Some(ids) = self.grid.get(&grid_index) {
+                    nearby_line_ids.extend(ids);
                 }
             }
         }
 
-        nearby_line_indices
+        nearby_line_ids
+    }
+}
+
+/// A set of grid cells.
+pub struct GridRegion(HashSet<GridIndex>);
+
+impl GridRegion {
+    pub(crate) fn cells(&self) -> impl Iterator<Item = &GridIndex> {
+        self.0.iter()
     }
 }
 
 #[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Default)]
-struct GridIndex(i64, i64);
+pub(crate) struct GridIndex(i64, i64);
 
 impl GridIndex {
+    /// Returns the cells covered by the rectangle with corners `p1` and `p2`, or None
+    /// if there are more than `limit` of them.
+    pub(crate) fn cells_in_box(
+        p1: Vector2D,
+        p2: Vector2D,
+        limit: usize,
+    ) -> Option<impl Iterator<Item = GridIndex>> {
+        let GridIndex(x1, y1) = GridIndex::from_location(p1);
+        let GridIndex(x2, y2) = GridIndex::from_location(p2);
+        let (x_range, y_range) = (x1.min(x2)..=x1.max(x2), y1.min(y2)..=y1.max(y2));
+
+        let count = (x_range.end() - x_range.start() + 1)
+            .checked_mul(y_range.end() - y_range.start() + 1)?;
+        if count > limit as i64 {
+            return None;
+        }
+
+        Some(x_range.flat_map(move |x| y_range.clone().map(move |y| GridIndex(x, y))))
+    }
+
     fn from_location(loc: Vector2D) -> GridIndex {
         GridIndex(
             (loc.0.floor() as i64).div_euclid(CELL_SIZE),
@@ -148,7 +268,39 @@ impl GridIndex {
         )
     }
 
-    fn iter_over_line(line: &Line) -> GridIndexLineIter {
+    /// Returns the position of `loc` within its cell.
+    fn remainder(self, loc: Vector2D) -> Vector2D {
+        Vector2D(
+            loc.0 - (self.0 * CELL_SIZE) as f64,
+            loc.1 - (self.1 * CELL_SIZE) as f64,
+        )
+    }
+
+    fn iter_over_line(line: &Line, version: GridVersion) -> Box<dyn Iterator<Item = GridIndex>> {
+        match version {
+            GridVersion::V6_1 => Box::new(Self::iter_over_line_classic(line)),
+            GridVersion::V6_2 => Box::new(Self::iter_over_line_precise(line)),
+        }
+    }
+
+    fn iter_over_line_classic(line: &Line) -> ClassicGridIndexLineIter {
+        let start = line.ends.0.location;
+        let start_cell = GridIndex::from_location(start);
+        let end_cell = GridIndex::from_location(line.ends.1.location);
+        let direction = line.as_vector2d();
+
+        ClassicGridIndexLineIter {
+            direction,
+            current_point: start,
+            current_cell: start_cell,
+            min: GridIndex(start_cell.0.min(end_cell.0), start_cell.1.min(end_cell.1)),
+            max: GridIndex(start_cell.0.max(end_cell.0), start_cell.1.max(end_cell.1)),
+            started: false,
+            finished: start_cell == end_cell || direction == Vector2D(0.0, 0.0),
+        }
+    }
+
+    fn iter_over_line_precise(line: &Line) -> GridIndexLineIter {
         let points = line.ends;
 
         let furthest_left = [points.0.location, points.1.location]
@@ -169,6 +321,86 @@ impl GridIndex {
     }
 }
 
+/// An iterator over the grid indices that a line is registered in for 6.1 tracks. Starting from the first point of the line, it repeatedly steps to the
+/// next cell border, overshooting by one unit when moving left or up.
+struct ClassicGridIndexLineIter {
+    direction: Vector2D,
+    current_point: Vector2D,
+    current_cell: GridIndex,
+
+    min: GridIndex,
+    max: GridIndex,
+
+    started: bool,
+    finished: bool,
+}
+
+impl ClassicGridIndexLineIter {
+    fn contains(&self, cell: GridIndex) -> bool {
+        self.min.0 <= cell.0 && cell.0 <= self.max.0 && self.min.1 <= cell.1 && cell.1 <= self.max.1
+    }
+
+    fn next_point(&self) -> Vector2D {
+        let Vector2D(x, y) = self.current_point;
+        let remainder = self.current_cell.remainder(self.current_point);
+        let cell_size = CELL_SIZE as f64;
+
+        let dif_x = if self.direction.0 > 0.0 {
+            cell_size - remainder.0
+        } else {
+            -1.0 - remainder.0
+        };
+        let dif_y = if self.direction.1 > 0.0 {
+            cell_size - remainder.1
+        } else {
+            -1.0 - remainder.1
+        };
+
+        if self.direction.0 == 0.0 {
+            return Vector2D(x, y + dif_y);
+        }
+        if self.direction.1 == 0.0 {
+            return Vector2D(x + dif_x, y);
+        }
+
+        let slope = self.direction.1 / self.direction.0;
+        let y_intercept = y - slope * x;
+        let next_y = slope * (x + dif_x) + y_intercept;
+
+        if (next_y - y).abs() < dif_y.abs() {
+            Vector2D(x + dif_x, next_y)
+        } else if (next_y - y).abs() == dif_y.abs() {
+            Vector2D(x + dif_x, y + dif_y)
+        } else {
+            Vector2D((y + dif_y - y_intercept) / slope, y + dif_y)
+        }
+    }
+}
+
+impl Iterator for ClassicGridIndexLineIter {
+    type Item = GridIndex;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            return Some(self.current_cell);
+        }
+        if self.finished {
+            return None;
+        }
+
+        self.current_point = self.next_point();
+        self.current_cell = GridIndex::from_location(self.current_point);
+
+        if self.contains(self.current_cell) {
+            Some(self.current_cell)
+        } else {
+            self.finished = true;
+            None
+        }
+    }
+}
+
 /// An iterator which iterates over the grid indices that a line intersects.
 struct GridIndexLineIter {
     current_point: Vector2D,
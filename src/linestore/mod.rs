@@ -5,9 +5,9 @@ mod raw_store;
 mod tests {
     use std::collections::HashSet;
 
-    use crate::game::Line;
     use crate::game::Vector2D;
-    use crate::linestore::grid::Grid;
+    use crate::game::{Line, LineId};
+    use crate::linestore::grid::{Grid, GridVersion};
 
     #[test]
     fn infinite_slope_line() {
@@ -247,4 +247,127 @@ mod tests {
             assert_eq!(lines, Vec::<&Line>::new());
         }
     }
+
+    #[test]
+    fn line_ids_survive_removal() {
+        let line1 = Line::builder().point(0.0, 0.0).point(100.0, 0.0).build();
+        let line2 = Line::builder().point(1.0, 0.0).point(100.0, 0.0).build();
+        let line3 = Line::builder().point(2.0, 0.0).point(100.0, 0.0).build();
+
+        let mut grid = Grid::default();
+        let id1 = grid.add_line(line1);
+        let id2 = grid.add_line(line2);
+        let id3 = grid.add_line(line3);
+
+        assert_eq!(grid.remove_line_by_id(id1), Some(line1));
+        assert_eq!(grid.line(id1), None);
+        assert_eq!(grid.line(id2), Some(&line2));
+        assert_eq!(grid.line(id3), Some(&line3));
+
+        let id4 = grid.add_line(line1);
+        assert_ne!(id4, id1);
+        assert_eq!(grid.line(id4), Some(&line1));
+    }
+
+    #[test]
+    fn identical_lines_have_different_ids() {
+        let line = Line::builder().point(0.0, 0.0).point(100.0, 0.0).build();
+
+        let mut grid = Grid::default();
+        let id1 = grid.add_line(line);
+        let id2 = grid.add_line(line);
+        assert_ne!(id1, id2);
+
+        grid.remove_line_by_id(id2);
+        assert_eq!(grid.all_ids(), &vec![id1]);
+        assert_eq!(grid.lines_near(Vector2D(50.0, 0.0), 1), vec![&line]);
+    }
+
+    #[test]
+    fn update_line_moves_line() {
+        let line = Line::builder().point(0.0, 0.0).point(100.0, 0.0).build();
+        let moved = Line::builder()
+            .point(0.0, 1000.0)
+            .point(100.0, 1000.0)
+            .build();
+
+        let mut grid = Grid::default();
+        let id = grid.add_line(line);
+
+        assert_eq!(grid.update_line(id, moved), Some(line));
+        assert_eq!(grid.update_line(LineId(id.0 + 1), moved), None);
+        assert_eq!(grid.line(id), Some(&moved));
+        assert_eq!(grid.lines_near(Vector2D(50.0, 0.0), 1), Vec::<&Line>::new());
+        assert_eq!(grid.lines_near(Vector2D(50.0, 1000.0), 1), vec![&moved]);
+    }
+
+    #[test]
+    fn add_line_with_taken_id() {
+        let line = Line::builder().point(0.0, 0.0).point(100.0, 0.0).build();
+
+        let mut grid = Grid::default();
+        assert_eq!(grid.add_line_with_id(LineId(5), line), Some(()));
+        assert_eq!(grid.add_line_with_id(LineId(5), line), None);
+        assert_eq!(grid.add_line(line), LineId(6));
+    }
+
+    #[test]
+    fn add_line_after_largest_id() {
+        let line = Line::builder().point(0.0, 0.0).point(100.0, 0.0).build();
+
+        let mut grid = Grid::default();
+        grid.add_line_with_id(LineId(1), line);
+        grid.add_line_with_id(LineId(u64::MAX), line);
+        assert_eq!(grid.add_line(line), LineId(0));
+        assert_eq!(grid.add_line(line), LineId(2));
+    }
+
+    #[test]
+    fn lines_near_ordered_by_descending_id() {
+        let line1 = Line::builder().point(0.0, 0.0).point(100.0, 0.0).build();
+        let line2 = Line::builder().point(1.0, 0.0).point(100.0, 0.0).build();
+        let line3 = Line::builder().point(2.0, 0.0).point(100.0, 0.0).build();
+
+        let mut grid = Grid::default();
+        grid.add_line_with_id(LineId(7), line2);
+        grid.add_line_with_id(LineId(2), line1);
+        grid.add_line_with_id(LineId(30), line3);
+
+        let expected = vec![&line3, &line2, &line1];
+        assert_eq!(grid.lines_near(Vector2D(50.0, 0.0), 1), expected);
+        assert_eq!(
+            grid.lines_near_box(Vector2D(0.0, 0.0), Vector2D(100.0, 0.0)),
+            expected
+        );
+    }
+
+    #[test]
+    fn classic_grid_skips_clipped_corner() {
+        // crosses x=20 at y=20.25, just inside the bottom-left cell
+        let line = Line::builder().point(30.0, 30.0).point(10.0, 10.5).build();
+        let clipped_cell = Vector2D(15.0, 25.0);
+
+        let grid = Grid::new_with_version(vec![line], GridVersion::V6_2);
+        assert_eq!(grid.lines_near(clipped_cell, 0), vec![&line]);
+
+        let grid = Grid::new_with_version(vec![line], GridVersion::V6_1);
+        assert_eq!(grid.lines_near(clipped_cell, 0), Vec::<&Line>::new());
+        assert_eq!(grid.lines_near(Vector2D(15.0, 15.0), 0), vec![&line]);
+        assert_eq!(grid.lines_near(Vector2D(25.0, 25.0), 0), vec![&line]);
+    }
+
+    #[test]
+    fn set_grid_version_reregisters_lines() {
+        let line = Line::builder().point(30.0, 30.0).point(10.0, 10.5).build();
+
+        let mut grid = Grid::new_with_version(vec![line], GridVersion::V6_1);
+        grid.set_version(GridVersion::V6_2);
+        assert_eq!(grid.lines_near(Vector2D(15.0, 25.0), 0), vec![&line]);
+
+        grid.set_version(GridVersion::V6_1);
+        assert_eq!(
+            grid.lines_near(Vector2D(15.0, 25.0), 0),
+            Vec::<&Line>::new()
+        );
+    }
 }
@@ -1,80 +1,105 @@
-use crate::game::Line;
+use crate::game::{Line, LineId};
 use std::collections::HashMap;
 
-#[derive(Eq, PartialEq, Hash, Clone, Copy, Default, Debug)]
-pub struct StoreIndex(usize);
-
 /// A data structure that holds an unordered list of lines, with duplicates.
+/// Each line is identified by a [`LineId`] which does not change when other
+/// lines are removed.
 #[derive(Eq, PartialEq, Clone, Default, Debug)]
 pub struct RawStore {
     lines: Vec<Line>,
-    line_to_index: HashMap<Line, Vec<usize>>,
+    ids: Vec<LineId>,
+    id_to_index: HashMap<LineId, usize>,
+    line_to_ids: HashMap<Line, Vec<LineId>>,
+    next_id: u64,
 }
 
 impl RawStore {
-    /// Returns all lines with their indices
+    /// Returns all lines, in the same order as [`RawStore::all_ids`]
     pub fn all_lines(&self) -> &Vec<Line> {
         &self.lines
     }
 
-    pub fn line_at(&self, idx: StoreIndex) -> Option<&Line> {
-        self.lines.get(idx.0)
+    /// Returns the ids of all lines, in the same order as [`RawStore::all_lines`]
+    pub fn all_ids(&self) -> &Vec<LineId> {
+        &self.ids
     }
 
-    /// Returns the index of the added line
-    pub fn add_line(&mut self, line: Line) -> StoreIndex {
-        self.lines.push(line);
-        let idx = self.lines.len() - 1;
-
-        self.line_to_index.entry(line).or_default().push(idx);
+    pub fn line(&self, id: LineId) -> Option<&Line> {
+        self.lines.get(*self.id_to_index.get(&id)?)
+    }
 
-        StoreIndex(idx)
+    /// Returns the id of a line equal to `line`, if there is one.
+    pub fn id_of(&self, line: &Line) -> Option<LineId> {
+        self.line_to_ids.get(line)?.first().copied()
     }
 
-    /// Removes a line from the store.
-    ///
-    /// If a swap_remove occurred such that the user of the
-    /// RawStore should need to update its indices, it returns those indices.
-    pub fn remove_line(&mut self, line: &Line) -> RemoveLineResult {
-        let idxs = self.line_to_index.get_mut(line);
-        if idxs.is_none() {
-            return RemoveLineResult::NoneRemoved;
+    /// Returns the id of the added line. This is one more than the largest id ever
+    /// used, or the smallest unused id once `u64::MAX` is taken.
+    pub fn add_line(&mut self, line: Line) -> LineId {
+        let mut id = LineId(self.next_id);
+        if self.id_to_index.contains_key(&id) {
+            id = (0..)
+                .map(LineId)
+                .find(|id| !self.id_to_index.contains_key(id))
+                .expect("every line id is taken");
         }
-        let idxs = idxs.unwrap();
+        self.insert(id, line);
+
+        id
+    }
 
-        // remove the line
-        let idx = idxs.swap_remove(0);
-        if idxs.is_empty() {
-            self.line_to_index.remove_entry(line);
+    /// Adds a line with a specific id. Returns None if the id is already taken.
+    pub fn add_line_with_id(&mut self, id: LineId, line: Line) -> Option<()> {
+        if self.id_to_index.contains_key(&id) {
+            return None;
         }
+        self.insert(id, line);
 
-        self.lines.swap_remove(idx);
+        Some(())
+    }
 
-        if self.lines.len() == idx {
-            return RemoveLineResult::RemovedNoSwap(StoreIndex(idx));
-        }
+    /// Removes a line from the store, returning the removed line.
+    pub fn remove_line(&mut self, id: LineId) -> Option<Line> {
+        let idx = self.id_to_index.remove(&id)?;
+
+        let line = self.lines.swap_remove(idx);
+        self.ids.swap_remove(idx);
+        self.forget_line_id(&line, id);
 
-        // since we did a swap_remove, update the line that used to be at lines.len and set it to idx
-        let line = self.lines.get(idx).unwrap();
-        self.line_to_index
-            .get_mut(line)
-            .unwrap()
-            .iter_mut()
-            .for_each(|each_idx| {
-                if *each_idx == self.lines.len() {
-                    *each_idx = idx
-                }
-            });
-
-        RemoveLineResult::RemovedAndNeedsSwap {
-            from: StoreIndex(self.lines.len()),
-            to: StoreIndex(idx),
+        // since we did a swap_remove, the line that used to be last is now at idx
+        if let Some(moved_id) = self.ids.get(idx) {
+            self.id_to_index.insert(*moved_id, idx);
         }
+
+        Some(line)
     }
-}
 
-pub enum RemoveLineResult {
-    NoneRemoved,
-    RemovedAndNeedsSwap { from: StoreIndex, to: StoreIndex },
-    RemovedNoSwap(StoreIndex),
+    /// Replaces the line with the given id, returning the previous line.
+    pub fn update_line(&mut self, id: LineId, line: Line) -> Option<Line> {
+        let idx = *self.id_to_index.get(&id)?;
+
+        let old_line = std::mem::replace(&mut self.lines[idx], line);
+        self.forget_line_id(&old_line, id);
+        self.line_to_ids.entry(line).or_default().push(id);
+
+        Some(old_line)
+    }
+
+    fn insert(&mut self, id: LineId, line: Line) {
+        self.lines.push(line);
+        self.ids.push(id);
+        self.id_to_index.insert(id, self.lines.len() - 1);
+        self.line_to_ids.entry(line).or_default().push(id);
+
+        self.next_id = self.next_id.max(id.0.saturating_add(1));
+    }
+
+    fn forget_line_id(&mut self, line: &Line, id: LineId) {
+        if let Some(ids) = self.line_to_ids.get_mut(line) {
+            ids.retain(|each_id| *each_id != id);
+            if ids.is_empty() {
+                self.line_to_ids.remove(line);
+            }
+        }
+    }
 }
@@ -1,16 +1,101 @@
-use crate::physics::entity_physics::UpdateBonesResult;
-use crate::rider::Entity;
+use std::cmp::Reverse;
+
+use crate::physics::entity_physics::{EntityEvents, UpdateBonesResult};
+use crate::physics::events::Event;
+use crate::physics::line_physics::Contact;
+use crate::physics::settings::FrameSettings;
+use crate::rider::{Entity, EntityId};
 use crate::Track;
 
-/// Runs the entire physics engine on a frame to get the next frame.
+/// Runs the entire physics engine on a frame to get the next frame, using the
+/// settings in the track's [`TrackMeta`](crate::TrackMeta). Triggers are not applied.
+///
+/// Entities keep their ids, except for entities which split, whose halves are given
+/// ids which are not used by any other entity.
 pub fn frame_after(riders: &[Entity], track: &Track) -> Vec<Entity> {
-    riders
-        .iter()
-        .flat_map(|entity| match entity.clone().apply_all_physics_ez(track) {
-            UpdateBonesResult::Same(bosh_sled) => vec![bosh_sled],
-            UpdateBonesResult::Broken(bosh, sled) => {
-                vec![bosh, sled]
+    let settings = FrameSettings::from_meta(&track.meta);
+    frame_after_with_settings(riders, track, &settings, 0, None)
+}
+
+/// Like [`frame_after`], but also returns the events that happened while simulating
+/// the next frame, which has the zero-indexed frame number `frame`.
+pub fn frame_after_with_events(
+    riders: &[Entity],
+    track: &Track,
+    frame: usize,
+) -> (Vec<Entity>, Vec<Event>) {
+    let settings = FrameSettings::from_meta(&track.meta);
+    let mut events = Vec::new();
+    let next_riders = frame_after_with_settings(riders, track, &settings, frame, Some(&mut events));
+
+    (next_riders, events)
+}
+
+/// Like [`frame_after`], but uses `settings` instead of the settings of the track. If
+/// `events` is given, the events that happened while simulating the next frame, which
+/// has the zero-indexed frame number `frame`, are added to it.
+pub fn frame_after_with_settings(
+    riders: &[Entity],
+    track: &Track,
+    settings: &FrameSettings,
+    frame: usize,
+    mut events: Option<&mut Vec<Event>>,
+) -> Vec<Entity> {
+    let mut next_riders = Vec::with_capacity(riders.len());
+    let mut entity_events = EntityEvents::default();
+
+    for entity in riders {
+        entity_events.contacts.clear();
+        entity_events.dismount = None;
+        let result = entity.clone().apply_all_physics_with_events(
+            track,
+            settings,
+            events.is_some().then_some(&mut entity_events),
+        );
+        match result {
+            UpdateBonesResult::Same(bosh_sled) => next_riders.push(bosh_sled),
+            UpdateBonesResult::Broken(parts) => {
+                for mut part in parts {
+                    part.id = EntityId::unused(riders.iter().chain(&next_riders));
+                    next_riders.push(part);
+                }
             }
-        })
-        .collect()
+        }
+
+        let Some(events) = events.as_deref_mut() else {
+            continue;
+        };
+        let contacts = &mut entity_events.contacts;
+        dedup_contacts(contacts);
+        events.extend(contacts.iter().map(|contact| Event::Contact {
+            frame,
+            entity: entity.id,
+            point: contact.point,
+            line: contact.line,
+            line_type: contact.line_type,
+            depth: contact.depth,
+        }));
+        if let Some(cause) = entity_events.dismount {
+            events.push(Event::Dismount {
+                frame,
+                entity: entity.id,
+                cause,
+            });
+        }
+    }
+
+    next_riders
+}
+
+/// Orders contacts by point, then by the order the lines were applied in, keeping
+/// only the deepest contact between each point and line.
+fn dedup_contacts(contacts: &mut Vec<Contact>) {
+    contacts.sort_by_key(|c| (c.point, Reverse(c.line)));
+    contacts.dedup_by(|later, earlier| {
+        let same = later.point == earlier.point && later.line == earlier.line;
+        if same {
+            earlier.depth = earlier.depth.max(later.depth);
+        }
+        same
+    });
 }
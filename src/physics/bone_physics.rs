@@ -1,6 +1,15 @@
 use crate::game::Vector2D;
 use crate::rider::{Bone, BoneType, Entity, Joint};
 
+/// How much more a mount bone can stretch before breaking while remounting.
+///
+/// Like the remount timers in `entity_physics`, this has not been checked against a
+/// remount recorded on linerider.com yet.
+pub const REMOUNT_ENDURANCE_MULTIPLIER: f64 = 2.0;
+/// How much weaker mount bones are at pulling points together while remounting.
+/// Unverified, like [`REMOUNT_ENDURANCE_MULTIPLIER`].
+pub const REMOUNT_STRENGTH_MULTIPLIER: f64 = 0.5;
+
 /// Returns Some((p1, p2)) for the bone-bounded locations, or None if the bone should break.
 /// Noteworthy that only bones of type Mount are breakable.
 pub fn next_bone_locations(bone: &Bone, entity: &Entity) -> Option<(Vector2D, Vector2D)> {
@@ -37,6 +46,32 @@ pub fn next_bone_locations(bone: &Bone, entity: &Entity) -> Option<(Vector2D, Ve
     }
 }
 
+/// Same as [`next_bone_locations`], except that mount bones are weakened and more
+/// enduring, as they are when a bosh is remounting its sled.
+pub fn next_remounting_bone_locations(
+    bone: &Bone,
+    entity: &Entity,
+) -> Option<(Vector2D, Vector2D)> {
+    let BoneType::Mount { endurance } = bone.bone_type else {
+        return next_bone_locations(bone, entity);
+    };
+
+    let p1 = entity.point_at(bone.p1);
+    let p2 = entity.point_at(bone.p2);
+
+    let length = p2.location.distance_squared(p1.location).sqrt();
+    let diff = get_diff(bone.resting_length, length);
+    if diff > endurance * REMOUNT_ENDURANCE_MULTIPLIER * bone.resting_length * 0.5 {
+        None
+    } else {
+        Some(bone_resolve(
+            p1.location,
+            p2.location,
+            diff * REMOUNT_STRENGTH_MULTIPLIER,
+        ))
+    }
+}
+
 pub fn joint_should_break(joint: &Joint, entity: &Entity) -> bool {
     let p1 = entity.point_at(joint.pair1.0);
     let p2 = entity.point_at(joint.pair1.1);
@@ -46,6 +81,20 @@ pub fn joint_should_break(joint: &Joint, entity: &Entity) -> bool {
     (p2.location - p1.location).cross_product_length(q2.location - q1.location) < 0.0
 }
 
+/// Returns the location of the loose end of a chain bone. A chain bone only pulls
+/// when it is stretched past its resting length, in which case the loose end is
+/// pulled back to that length without moving the point it hangs from. Otherwise
+/// the chain is slack and the loose end stays where it is.
+pub fn next_chain_location(anchor: Vector2D, loose: Vector2D, resting_length: f64) -> Vector2D {
+    let delta = anchor - loose;
+    let length = delta.length_squared().sqrt();
+    if length <= resting_length {
+        loose
+    } else {
+        loose + delta * ((length - resting_length) / length)
+    }
+}
+
 fn bone_resolve(p1: Vector2D, p2: Vector2D, diff: f64) -> (Vector2D, Vector2D) {
     let delta = (p1 - p2) * diff;
 
@@ -0,0 +1,250 @@
+//! Runs the tracks in the `conformance` directory and compares every recorded
+//! rider state to the one simulated by bosh-rs.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::formats::json::JsonTrack;
+use crate::game::{Track, Vector2D};
+use crate::rider::{Entity, PointIndex};
+
+#[derive(Deserialize)]
+struct GoldenTrack {
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    source: String,
+    track: JsonTrack,
+    #[serde(default)]
+    tolerance: f64,
+    frames: Vec<GoldenFrame>,
+}
+
+#[derive(Deserialize)]
+struct GoldenFrame {
+    frame: usize,
+    riders: Vec<BTreeMap<PointIndex, GoldenPoint>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GoldenPoint {
+    location: Vector2D,
+    previous_location: Vector2D,
+    momentum: Option<Vector2D>,
+}
+
+/// Where a simulated track first differs from its recording.
+#[derive(Debug)]
+struct Divergence {
+    frame: usize,
+    rider: usize,
+    point: Option<PointIndex>,
+    message: String,
+}
+
+fn corpus_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("conformance")
+}
+
+fn read_golden(path: &Path) -> anyhow::Result<GoldenTrack> {
+    let file = fs::File::open(path)?;
+
+    serde_json::from_reader(file).context("invalid golden track")
+}
+
+/// Simulates `golden` up to its last recorded frame, returning the first recorded
+/// state which does not match.
+fn first_divergence(golden: &GoldenTrack) -> anyhow::Result<Option<Divergence>> {
+    let track = Track::try_from(golden.track.clone())?;
+
+    let mut expected: Vec<&GoldenFrame> = golden.frames.iter().collect();
+    expected.sort_by_key(|frame| frame.frame);
+    let Some(last) = expected.last().map(|frame| frame.frame) else {
+        return Ok(None);
+    };
+
+    let mut expected = expected.into_iter().peekable();
+    for (frame, riders) in track.frames().take(last + 1).enumerate() {
+        while let Some(golden_frame) = expected.next_if(|f| f.frame == frame) {
+            if let Some(divergence) = compare_frame(golden_frame, &riders, golden.tolerance) {
+                return Ok(Some(divergence));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn compare_frame(golden: &GoldenFrame, riders: &[Entity], tolerance: f64) -> Option<Divergence> {
+    let diverged = |rider, point, message| {
+        Some(Divergence {
+            frame: golden.frame,
+            rider,
+            point,
+            message,
+        })
+    };
+
+    if golden.riders.len() != riders.len() {
+        let message = format!(
+            "expected {} riders, got {}",
+            golden.riders.len(),
+            riders.len()
+        );
+        return diverged(0, None, message);
+    }
+
+    for (rider, (expected, actual)) in golden.riders.iter().zip(riders).enumerate() {
+        for (&index, expected) in expected {
+            let Some(actual) = actual.points.get(&index) else {
+                return diverged(rider, Some(index), "point is missing".to_string());
+            };
+
+            let mut fields = vec![
+                ("location", expected.location, actual.location),
+                (
+                    "previousLocation",
+                    expected.previous_location,
+                    actual.previous_location,
+                ),
+            ];
+            if let Some(momentum) = expected.momentum {
+                fields.push(("momentum", momentum, actual.momentum));
+            }
+
+            for (name, expected, actual) in fields {
+                let difference = expected - actual;
+                if difference.0.abs() > tolerance || difference.1.abs() > tolerance {
+                    let message = format!("{name}: expected {expected:?}, got {actual:?}");
+                    return diverged(rider, Some(index), message);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[test]
+fn conformance_corpus() {
+    let mut paths: Vec<PathBuf> = fs::read_dir(corpus_dir())
+        .expect("missing conformance directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    let mut failures = String::new();
+    for path in &paths {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let result = read_golden(path).and_then(|golden| {
+            let divergence = first_divergence(&golden)?;
+            Ok((golden, divergence))
+        });
+
+        match result {
+            Ok((_, None)) => {}
+            Ok((golden, Some(d))) => {
+                let point = d.point.map_or("-".to_string(), |p| p.to_string());
+                writeln!(
+                    failures,
+                    "{name} ({}, recorded from {}): frame {}, rider {}, point {point}: {}",
+                    golden.description, golden.source, d.frame, d.rider, d.message
+                )
+                .unwrap();
+            }
+            Err(err) => writeln!(failures, "{name}: {err:#}").unwrap(),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} conformance tracks diverged:\n{failures}",
+        failures.lines().count(),
+        paths.len()
+    );
+}
+
+#[test]
+fn divergence_reports_first_frame_and_point() {
+    let mut golden: GoldenTrack = serde_json::from_value(serde_json::json!({
+        "track": {
+            "version": "6.2",
+            "riders": [
+                { "startPosition": { "x": 0, "y": 0 }, "startVelocity": { "x": 0.4, "y": 0 } }
+            ],
+            "lines": [
+                {
+                    "id": 0, "type": 0,
+                    "x1": 1.2112666897140032, "y1": -3.0419052379903606,
+                    "x2": 2.7302375219426875, "y2": -2.021219126142812
+                }
+            ]
+        },
+        "frames": [
+            {
+                "frame": 10,
+                "riders": [
+                    {
+                        "SledTail": {
+                            "location": [14.127750467596647, -10.151751900395013],
+                            "previousLocation": [11.862884095165988, -10.089233973375801],
+                            "momentum": [2.2295236487517673, -0.3484422284398246]
+                        }
+                    }
+                ]
+            }
+        ]
+    }))
+    .unwrap();
+    let recorded = golden.frames.pop().unwrap();
+
+    // record the real states of frames 3 to 5, then nudge the shoulder on frame 4
+    let track = Track::try_from(golden.track.clone()).unwrap();
+    for frame in 3..=5 {
+        let riders = track.entity_positions_at(frame);
+        let points = riders[0]
+            .points
+            .iter()
+            .map(|(&index, p)| {
+                let golden_point = GoldenPoint {
+                    location: p.location,
+                    previous_location: p.previous_location,
+                    momentum: Some(p.momentum),
+                };
+                (index, golden_point)
+            })
+            .collect();
+        golden.frames.push(GoldenFrame {
+            frame,
+            riders: vec![points],
+        });
+    }
+    golden.frames.push(recorded);
+    assert!(first_divergence(&golden).unwrap().is_none());
+
+    let shoulder = golden.frames[1].riders[0]
+        .get_mut(&PointIndex::BoshShoulder)
+        .unwrap();
+    shoulder.location.1 += 1e-12;
+    golden.frames[2].riders[0]
+        .get_mut(&PointIndex::BoshButt)
+        .unwrap()
+        .location
+        .0 += 1.0;
+
+    let divergence = first_divergence(&golden).unwrap().unwrap();
+    assert_eq!(4, divergence.frame);
+    assert_eq!(Some(PointIndex::BoshShoulder), divergence.point);
+
+    golden.tolerance = 1e-9;
+    let divergence = first_divergence(&golden).unwrap().unwrap();
+    assert_eq!(5, divergence.frame);
+    assert_eq!(Some(PointIndex::BoshButt), divergence.point);
+}
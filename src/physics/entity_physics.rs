@@ -1,117 +1,359 @@
 use crate::game::Track;
 use crate::game::Vector2D;
-use crate::physics::bone_physics::{joint_should_break, next_bone_locations};
-use crate::physics::line_physics::apply_gravity_wells;
-use crate::rider::{Entity, EntityPoint};
+use crate::physics::bone_physics::{
+    joint_should_break, next_bone_locations, next_chain_location, next_remounting_bone_locations,
+};
+use crate::physics::events::DismountCause;
+use crate::physics::line_physics::{
+    apply_gravity_wells, apply_gravity_wells_with_contacts, Contact,
+};
+use crate::physics::settings::FrameSettings;
+use crate::rider::{Bone, BoneType, Entity, EntityPoint, Joint, MountState, Scarf};
 
 pub type PhysicsEntity = Entity;
 
+/// The number of frames after falling off before a bosh may start remounting.
+///
+/// Not yet checked against a remount recorded on linerider.com, so this may differ
+/// from linerider.com's dismount timer. See `conformance/README.md`.
+pub const DISMOUNT_FRAMES: u32 = 30;
+/// The number of frames that a bosh must stay on its sled while remounting before
+/// it is mounted again. Unverified, like [`DISMOUNT_FRAMES`].
+pub const REMOUNT_FRAMES: u32 = 3;
+
+/// The gravity used by [`PhysicsEntity::apply_all_physics_ez`].
+pub const DEFAULT_GRAVITY: Vector2D = Vector2D(0.0, 0.175);
+/// The number of iterations used by [`PhysicsEntity::apply_all_physics_ez`].
+pub const DEFAULT_ITERATIONS: u64 = 6;
+
 impl PhysicsEntity {
     /// Pushes the points of `self` in accordance to gravity well logic.
     pub fn apply_gravity_wells(&mut self, track: &Track) {
-        self.mutate_points(|p| apply_gravity_wells(p, track))
+        self.points
+            .values_mut()
+            .for_each(|p| apply_gravity_wells(p, track))
+    }
+
+    /// Like [`PhysicsEntity::apply_gravity_wells`], but uses `settings` instead of the
+    /// settings of the track, and if `contacts` is given, records every contact between
+    /// a point and a line in it.
+    pub fn apply_gravity_wells_with_contacts(
+        &mut self,
+        track: &Track,
+        settings: &FrameSettings,
+        mut contacts: Option<&mut Vec<Contact>>,
+    ) {
+        for (&index, point) in self.points.iter_mut() {
+            apply_gravity_wells_with_contacts(point, track, settings, |line, line_type, depth| {
+                if let Some(contacts) = contacts.as_deref_mut() {
+                    contacts.push(Contact {
+                        point: index,
+                        line,
+                        line_type,
+                        depth,
+                    })
+                }
+            });
+        }
     }
 
     /// Applies bone physics to a list of bones. Moves self because
     /// a BoshSled may break, causing `self` to become unusable.
-    pub fn apply_bones(mut self) -> UpdateBonesResult {
-        let mut broken = false;
-        for bone in self.bones.clone() {
-            if let Some((next_p1, next_p2)) = next_bone_locations(&bone, &self) {
+    pub fn apply_bones(self) -> UpdateBonesResult {
+        self.apply_bones_with_remount(false)
+    }
+
+    /// Applies bone physics to a list of bones. If `remount` is true, a breaking
+    /// mount bone dismounts the bosh instead of splitting the boshsled.
+    pub fn apply_bones_with_remount(self, remount: bool) -> UpdateBonesResult {
+        self.apply_bones_reporting(remount).0
+    }
+
+    /// Like [`PhysicsEntity::apply_bones_with_remount`], but also returns the first
+    /// bone that broke.
+    fn apply_bones_reporting(mut self, remount: bool) -> (UpdateBonesResult, Option<Bone>) {
+        let mut broken = None;
+        for i in 0..self.bones.len() {
+            let bone = self.bones[i];
+            let is_mount_bone = matches!(bone.bone_type, BoneType::Mount { .. });
+            let next_locations = match self.mount_state {
+                MountState::Dismounting(_) | MountState::Dismounted if is_mount_bone => continue,
+                MountState::Remounting(_) => next_remounting_bone_locations(&bone, &self),
+                _ => next_bone_locations(&bone, &self),
+            };
+
+            if let Some((next_p1, next_p2)) = next_locations {
                 self.point_at_mut(bone.p1).location = next_p1;
                 self.point_at_mut(bone.p2).location = next_p2;
             } else {
-                broken = true
+                broken.get_or_insert(bone);
             }
         }
 
-        if broken {
-            let (bosh, sled) = self.split();
-            UpdateBonesResult::Broken(bosh, sled)
-        } else {
+        let result = if broken.is_none() {
             UpdateBonesResult::Same(self)
-        }
+        } else if remount {
+            self.mount_state = match self.mount_state {
+                MountState::Remounting(_) => MountState::Dismounted,
+                _ => MountState::Dismounting(0),
+            };
+            UpdateBonesResult::Same(self)
+        } else {
+            UpdateBonesResult::Broken(self.split())
+        };
+
+        (result, broken)
     }
 
     /// Performs the logic of stepping the points of the rider to the next frame.
-    /// Does not actually do any physics besides applying gravity.
+    /// Does not actually do any physics besides applying gravity. The scarf is
+    /// not moved.
     pub fn next_points(&mut self, gravity: Vector2D) {
-        self.mutate_points(|p| {
-            let new_velocity = (p.location - p.previous_location) + gravity;
-
-            *p = EntityPoint {
-                previous_location: p.location,
-                location: p.location + new_velocity,
-                momentum: new_velocity,
-                friction: p.friction,
-            };
-        })
+        self.points
+            .values_mut()
+            .for_each(|p| *p = next_point(p, gravity, 1.0));
+    }
+
+    /// Moves the scarf forward by one frame, then pulls its stretched chain bones
+    /// back to their resting lengths, starting from the point the scarf hangs from. Does
+    /// nothing if the entity has no scarf or does not have the scarf's anchor.
+    pub fn apply_scarf_physics(&mut self, gravity: Vector2D) {
+        let Some(scarf) = &mut self.scarf else {
+            return;
+        };
+        let Some(anchor) = self.points.get(&scarf.anchor) else {
+            return;
+        };
+
+        let mut previous = anchor.location;
+        for p in scarf.points.iter_mut() {
+            *p = next_point(p, gravity, scarf.air_friction);
+            p.location = next_chain_location(previous, p.location, scarf.segment_length);
+            previous = p.location;
+        }
     }
 
     /// applies joint logic
     /// does nothing on non-boshsleds
     pub fn apply_all_joints(self) -> UpdateBonesResult {
-        if self.joints.iter().any(|j| joint_should_break(j, &self)) {
-            let (bosh, sled) = self.split();
-            UpdateBonesResult::Broken(bosh, sled)
+        self.apply_all_joints_with_remount(false)
+    }
+
+    /// Applies joint logic. If `remount` is true, the bosh dismounts instead of
+    /// splitting the boshsled, unless the sled itself broke.
+    pub fn apply_all_joints_with_remount(self, remount: bool) -> UpdateBonesResult {
+        self.apply_all_joints_reporting(remount).0
+    }
+
+    /// Like [`PhysicsEntity::apply_all_joints_with_remount`], but also returns the
+    /// joint that caused the bosh to fall off, if any.
+    fn apply_all_joints_reporting(mut self, remount: bool) -> (UpdateBonesResult, Option<Joint>) {
+        let mounted = self.mount_state == MountState::Mounted;
+        let sled_broken = self
+            .joints
+            .iter()
+            .find(|j| self.is_group_joint(j) && joint_should_break(j, &self))
+            .copied();
+        let dismounted = self
+            .joints
+            .iter()
+            .find(|j| mounted && !self.is_group_joint(j) && joint_should_break(j, &self))
+            .copied();
+
+        if let Some(joint) = sled_broken.or(dismounted.filter(|_| !remount)) {
+            (UpdateBonesResult::Broken(self.split()), Some(joint))
+        } else if let Some(joint) = dismounted {
+            self.mount_state = MountState::Dismounting(0);
+            (UpdateBonesResult::Same(self), Some(joint))
         } else {
-            UpdateBonesResult::Same(self)
+            (UpdateBonesResult::Same(self), None)
+        }
+    }
+
+    /// Moves the remount state machine forward by one frame. This runs after the
+    /// bones of the frame are applied, so mount bones are still ignored on the
+    /// frame that the bosh starts remounting.
+    pub fn advance_mount_state(&mut self) {
+        self.mount_state = match self.mount_state {
+            MountState::Mounted => MountState::Mounted,
+            MountState::Dismounting(frames) if frames + 1 >= DISMOUNT_FRAMES => {
+                MountState::Dismounted
+            }
+            MountState::Dismounting(frames) => MountState::Dismounting(frames + 1),
+            MountState::Dismounted if self.can_remount() => MountState::Remounting(0),
+            MountState::Dismounted => MountState::Dismounted,
+            MountState::Remounting(frames) if frames + 1 >= REMOUNT_FRAMES => MountState::Mounted,
+            MountState::Remounting(frames) => MountState::Remounting(frames + 1),
         }
     }
 
+    /// Returns true if every mount bone is close enough to its resting length that
+    /// it would not break while remounting.
+    fn can_remount(&self) -> bool {
+        self.bones
+            .iter()
+            .filter(|bone| matches!(bone.bone_type, BoneType::Mount { .. }))
+            .all(|bone| next_remounting_bone_locations(bone, self).is_some())
+    }
+
     /// Applies all physics steps to the rider in the correct order.
     /// Moves `self` because it may become unusable after the sled breaks.
     pub fn apply_all_physics_ez(self, track: &Track) -> UpdateBonesResult {
-        self.apply_all_physics(track, Vector2D(0.0, 0.175), 6)
+        self.apply_all_physics(track, DEFAULT_GRAVITY, DEFAULT_ITERATIONS)
     }
 
     /// Applies all physics steps to the rider in the correct order.
     /// Moves `self` because it may become unusable after the sled breaks.
     pub fn apply_all_physics(
-        mut self,
+        self,
         track: &Track,
         gravity: Vector2D,
         iterations: u64,
     ) -> UpdateBonesResult {
-        self.next_points(gravity);
+        let settings = FrameSettings {
+            gravity,
+            iterations,
+            ..FrameSettings::from_meta(&track.meta)
+        };
+        self.apply_all_physics_with_events(track, &settings, None)
+    }
+
+    /// Like [`PhysicsEntity::apply_all_physics`], but uses `settings` instead of the
+    /// settings of the track, and if `events` is given, records what happened to the
+    /// entity in it.
+    pub fn apply_all_physics_with_events(
+        mut self,
+        track: &Track,
+        settings: &FrameSettings,
+        events: Option<&mut EntityEvents>,
+    ) -> UpdateBonesResult {
+        // the scarf is simulated after the rest of the entity, so that it can hang
+        // from wherever its anchor ended up
+        let scarf = self.scarf.take();
+        let result = self.apply_body_physics(track, settings, events);
+
+        match scarf {
+            Some(scarf) => result.with_scarf(scarf, settings.gravity),
+            None => result,
+        }
+    }
+
+    /// Applies every physics step except for moving the scarf.
+    fn apply_body_physics(
+        mut self,
+        track: &Track,
+        settings: &FrameSettings,
+        mut events: Option<&mut EntityEvents>,
+    ) -> UpdateBonesResult {
+        let remount = track.meta.remount;
+
+        self.next_points(settings.gravity);
 
         let mut result = UpdateBonesResult::Same(self);
 
-        for _ in 0..iterations {
+        for _ in 0..settings.iterations {
             result = match result {
-                UpdateBonesResult::Same(same) => same.apply_bones(),
-                UpdateBonesResult::Broken(bosh, sled) => {
-                    let bosh = bosh.apply_bones().unwrap_same();
-                    let sled = sled.apply_bones().unwrap_same();
+                UpdateBonesResult::Same(same) => {
+                    let (result, broken_bone) = same.apply_bones_reporting(remount);
+                    if let (Some(bone), Some(events)) = (broken_bone, events.as_deref_mut()) {
+                        events
+                            .dismount
+                            .get_or_insert(DismountCause::MountBoneStretched(bone));
+                    }
 
-                    UpdateBonesResult::Broken(bosh, sled)
+                    result
                 }
+                UpdateBonesResult::Broken(parts) => UpdateBonesResult::Broken(
+                    parts
+                        .into_iter()
+                        .map(|part| part.apply_bones().unwrap_same())
+                        .collect(),
+                ),
             };
+            let mut contacts = events.as_deref_mut().map(|e| &mut e.contacts);
             match &mut result {
                 UpdateBonesResult::Same(same) => {
-                    same.apply_gravity_wells(track);
+                    same.apply_gravity_wells_with_contacts(track, settings, contacts);
                 }
-                UpdateBonesResult::Broken(bosh, sled) => {
-                    bosh.apply_gravity_wells(track);
-                    sled.apply_gravity_wells(track);
+                UpdateBonesResult::Broken(parts) => {
+                    for part in parts {
+                        part.apply_gravity_wells_with_contacts(
+                            track,
+                            settings,
+                            contacts.as_deref_mut(),
+                        );
+                    }
                 }
             }
         }
 
+        let UpdateBonesResult::Same(same) = result else {
+            return result;
+        };
+
+        let (result, crossed_joint) = same.apply_all_joints_reporting(remount);
+        if let (Some(joint), Some(events)) = (crossed_joint, events) {
+            events
+                .dismount
+                .get_or_insert(DismountCause::JointCrossed(joint));
+        }
+
         match result {
-            UpdateBonesResult::Same(same) => same.apply_all_joints(),
-            UpdateBonesResult::Broken(_, _) => result,
+            UpdateBonesResult::Same(mut same) => {
+                same.advance_mount_state();
+                UpdateBonesResult::Same(same)
+            }
+            broken => broken,
         }
     }
 }
 
+/// What happened to an entity during a single frame, recorded by
+/// [`PhysicsEntity::apply_all_physics_with_events`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntityEvents {
+    /// Every contact between a point and a line. A point touching the same line
+    /// during several iterations is recorded once for each iteration.
+    pub contacts: Vec<Contact>,
+    /// Why the bosh fell off its sled, if it did.
+    pub dismount: Option<DismountCause>,
+}
+
+// boxing the entity would cost an allocation for every entity on every frame
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone)]
 pub enum UpdateBonesResult {
     Same(PhysicsEntity),
-    Broken(PhysicsEntity, PhysicsEntity),
+    /// The entity broke apart into one entity per break group.
+    Broken(Vec<PhysicsEntity>),
 }
 
 impl UpdateBonesResult {
+    /// Gives `scarf` back to the entity that has its anchor and simulates it.
+    fn with_scarf(self, scarf: Scarf, gravity: Vector2D) -> UpdateBonesResult {
+        let attach = |mut entity: PhysicsEntity, scarf: &mut Option<Scarf>| {
+            if scarf
+                .as_ref()
+                .is_some_and(|s| entity.points.contains_key(&s.anchor))
+            {
+                entity.scarf = scarf.take();
+                entity.apply_scarf_physics(gravity);
+            }
+            entity
+        };
+
+        let mut scarf = Some(scarf);
+        match self {
+            UpdateBonesResult::Same(same) => UpdateBonesResult::Same(attach(same, &mut scarf)),
+            UpdateBonesResult::Broken(parts) => UpdateBonesResult::Broken(
+                parts
+                    .into_iter()
+                    .map(|part| attach(part, &mut scarf))
+                    .collect(),
+            ),
+        }
+    }
+
     pub fn unwrap_same(self) -> PhysicsEntity {
         if let UpdateBonesResult::Same(entity) = self {
             entity
@@ -120,3 +362,16 @@ impl UpdateBonesResult {
         }
     }
 }
+
+/// Moves a point forward by one frame, keeping `friction` of its velocity before
+/// adding gravity.
+fn next_point(p: &EntityPoint, gravity: Vector2D, friction: f64) -> EntityPoint {
+    let new_velocity = (p.location - p.previous_location) * friction + gravity;
+
+    EntityPoint {
+        previous_location: p.location,
+        location: p.location + new_velocity,
+        momentum: new_velocity,
+        friction: p.friction,
+    }
+}
@@ -0,0 +1,52 @@
+use crate::game::{LineId, LineType};
+use crate::rider::{Bone, EntityId, Joint, PointIndex};
+
+/// Something that happened to an entity while a frame was being simulated.
+///
+/// `entity` is the id the entity had in the frame before `frame`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// A point was pushed out of a line. Only the deepest contact between a point
+    /// and a line is reported for each frame.
+    Contact {
+        frame: usize,
+        entity: EntityId,
+        point: PointIndex,
+        line: LineId,
+        line_type: LineType,
+        /// How far below the line the point was before it was pushed out.
+        depth: f64,
+    },
+    /// A bosh fell off its sled, either splitting the entity in two or, on tracks
+    /// with remounting, starting to dismount.
+    Dismount {
+        frame: usize,
+        entity: EntityId,
+        cause: DismountCause,
+    },
+}
+
+/// Why a bosh fell off its sled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DismountCause {
+    /// A mount bone was stretched past its endurance.
+    MountBoneStretched(Bone),
+    /// The points of a joint crossed over each other.
+    JointCrossed(Joint),
+}
+
+impl Event {
+    /// The frame in which the event happened.
+    pub fn frame(&self) -> usize {
+        match *self {
+            Event::Contact { frame, .. } | Event::Dismount { frame, .. } => frame,
+        }
+    }
+
+    /// The id the entity had in the frame before [`Event::frame`].
+    pub fn entity(&self) -> EntityId {
+        match *self {
+            Event::Contact { entity, .. } | Event::Dismount { entity, .. } => entity,
+        }
+    }
+}
@@ -1,22 +1,50 @@
-use crate::game::LineType;
 use crate::game::Track;
-use crate::rider::EntityPoint;
+use crate::game::{LineId, LineType};
+use crate::physics::settings::FrameSettings;
+use crate::rider::{EntityPoint, PointIndex};
+
+/// A point of an entity being pushed by a line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub point: PointIndex,
+    pub line: LineId,
+    pub line_type: LineType,
+    /// How far below the line the point was before it was pushed out.
+    pub depth: f64,
+}
 
 pub fn apply_gravity_wells(point: &mut EntityPoint, track: &Track) {
-    for line in &track.lines_near(point.location) {
-        if matches!(line.line_type, LineType::Scenery) {
+    let settings = FrameSettings::from_meta(&track.meta);
+    apply_gravity_wells_with_contacts(point, track, &settings, |_, _, _| {})
+}
+
+/// Like [`apply_gravity_wells`], but uses `settings` instead of the settings of the
+/// track, and calls `on_contact` with every line which pushes the point, the type it
+/// behaved as, and how far below the line the point was.
+pub fn apply_gravity_wells_with_contacts(
+    point: &mut EntityPoint,
+    track: &Track,
+    settings: &FrameSettings,
+    mut on_contact: impl FnMut(LineId, LineType, f64),
+) {
+    for (id, line) in track.lines_near_with_ids(point.location) {
+        let line_type = settings.line_type(id, line);
+        if matches!(line_type, LineType::Scenery) {
             continue;
         }
         let distance_below = track.distance_below_line(line, point);
         if distance_below == 0.0 {
             continue;
         }
+        on_contact(id, line_type, distance_below);
         let perpendicular = line.perpendicular();
 
         let next_location = point.location + (perpendicular * distance_below);
 
-        let mut friction_adjustment =
-            perpendicular.rotate90_right() * point.friction * distance_below;
+        let mut friction_adjustment = perpendicular.rotate90_right()
+            * point.friction
+            * settings.friction_scale
+            * distance_below;
         if point.previous_location.0 >= next_location.0 {
             friction_adjustment.0 = -friction_adjustment.0;
         }
@@ -27,7 +55,7 @@ pub fn apply_gravity_wells(point: &mut EntityPoint, track: &Track) {
         point.previous_location += friction_adjustment;
         point.location = next_location;
 
-        if let LineType::Accelerate { amount: accel } = line.line_type {
+        if let LineType::Accelerate { amount: accel } = line_type {
             let direction = if line.flipped { 1.0 } else { -1.0 };
 
             point.previous_location +=
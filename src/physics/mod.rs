@@ -1,37 +1,41 @@
 pub mod advance_frame;
 pub mod bone_physics;
 pub mod entity_physics;
+pub mod events;
 pub mod line_physics;
+pub mod settings;
 
 #[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
+mod conformance;
 
+#[cfg(test)]
+mod tests {
     use crate::game::Line;
+    use crate::game::LineId;
     use crate::game::Track;
     use crate::game::Vector2D;
+    use crate::physics::advance_frame::{frame_after, frame_after_with_events};
+    use crate::physics::entity_physics::{DISMOUNT_FRAMES, REMOUNT_FRAMES};
+    use crate::physics::events::{DismountCause, Event};
     use crate::physics::line_physics::apply_gravity_wells;
-    use crate::rider::{Bone, BoneType, Entity, EntityPoint, PointIndex};
-    use crate::LineType;
+    use crate::rider::{
+        Bone, BoneType, Entity, EntityId, EntityPoint, MountState, PointIndex, PointMap, Scarf,
+        Skeleton, INLINE_SCARF_POINTS,
+    };
+    use crate::test_tracks::{avg_velocity, floor_track, rider_track, wall_crash_track};
+    use crate::{CachePolicy, LineType, TrackMeta};
 
     fn _avg_position(entity: &Entity) -> Vector2D {
         let bosh_sum: Vector2D = entity.points.values().map(|p| p.location).sum();
         bosh_sum / entity.points.len() as f64
     }
 
-    fn avg_velocity(entity: &Entity) -> Vector2D {
-        let bosh_sum: Vector2D = entity
-            .points
-            .values()
-            .map(|p| (p.location - p.previous_location))
-            .sum();
-        bosh_sum / entity.points.len() as f64
-    }
-
     #[test]
     fn update_bones_contract() {
         let bosh = Entity {
-            points: HashMap::from([
+            id: Default::default(),
+            parent: None,
+            points: PointMap::from([
                 (
                     PointIndex::BoshShoulder,
                     EntityPoint {
@@ -56,15 +60,19 @@ mod tests {
                 p2: PointIndex::BoshButt,
                 resting_length: 5.0,
                 bone_type: BoneType::Normal,
-            }],
-            joints: vec![],
+            }]
+            .into(),
+            joints: vec![].into(),
+            mount_state: Default::default(),
+            scarf: None,
+            break_groups: vec![].into(),
         };
 
         let bosh = bosh.apply_bones().unwrap_same();
 
         assert_eq!(
             bosh.points,
-            HashMap::from([
+            PointMap::from([
                 (
                     PointIndex::BoshShoulder,
                     EntityPoint {
@@ -90,7 +98,9 @@ mod tests {
     #[test]
     fn update_bones_expand() {
         let bosh = Entity {
-            points: HashMap::from([
+            id: Default::default(),
+            parent: None,
+            points: PointMap::from([
                 (
                     PointIndex::BoshShoulder,
                     EntityPoint {
@@ -115,15 +125,19 @@ mod tests {
                 p2: PointIndex::BoshButt,
                 resting_length: 5.0,
                 bone_type: BoneType::Normal,
-            }],
-            joints: vec![],
+            }]
+            .into(),
+            joints: vec![].into(),
+            mount_state: Default::default(),
+            scarf: None,
+            break_groups: vec![].into(),
         };
 
         let bosh = bosh.apply_bones().unwrap_same();
 
         assert_eq!(
             bosh.points,
-            HashMap::from([
+            PointMap::from([
                 (
                     PointIndex::BoshShoulder,
                     EntityPoint {
@@ -217,7 +231,7 @@ mod tests {
         );
 
         let entities = track.entity_positions_at(10);
-        let entity = entities.get(0).unwrap();
+        let entity = entities.first().unwrap();
 
         // comparison is to linerider.com's physics
         assert_eq!(
@@ -244,7 +258,7 @@ mod tests {
         );
 
         let entities = track.entity_positions_at(10);
-        let entity = entities.get(0).unwrap();
+        let entity = entities.first().unwrap();
 
         // comparison is to linerider.com's physics
         assert_eq!(
@@ -257,4 +271,706 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn rider_crash_without_remount_splits() {
+        let track = wall_crash_track(false);
+
+        assert_eq!(2, track.entity_positions_at(10).len());
+    }
+
+    #[test]
+    fn rider_crash_with_remount_dismounts() {
+        let track = wall_crash_track(true);
+
+        let entities = track.entity_positions_at(10);
+        assert_eq!(1, entities.len());
+        assert!(matches!(
+            entities[0].mount_state,
+            MountState::Dismounting(_)
+        ));
+
+        let entities = track.entity_positions_at(10 + DISMOUNT_FRAMES as usize);
+        assert_eq!(entities[0].mount_state, MountState::Dismounted);
+    }
+
+    #[test]
+    fn rider_remounts_when_in_range() {
+        let mut entity = Entity::default_boshsled();
+        entity.mount_state = MountState::Dismounted;
+        let track = rider_track(entity, vec![], |meta| meta.remount = true);
+
+        assert_eq!(
+            track.entity_positions_at(1)[0].mount_state,
+            MountState::Remounting(0)
+        );
+        assert_eq!(
+            track.entity_positions_at(1 + REMOUNT_FRAMES as usize)[0].mount_state,
+            MountState::Mounted
+        );
+    }
+
+    #[test]
+    fn rider_does_not_remount_out_of_range() {
+        let mut entity = Entity::default_boshsled();
+        entity.mount_state = MountState::Dismounted;
+        for (_, p) in entity.points.iter_mut().filter(|(i, _)| i.is_bosh()) {
+            p.location += Vector2D(0.0, -50.0);
+            p.previous_location += Vector2D(0.0, -50.0);
+        }
+        let track = rider_track(entity, vec![], |meta| meta.remount = true);
+
+        assert_eq!(
+            track.entity_positions_at(10)[0].mount_state,
+            MountState::Dismounted
+        );
+    }
+
+    #[test]
+    fn remounting_pulls_bosh_back_onto_sled() {
+        let mut entity = Entity::default_boshsled();
+        entity.mount_state = MountState::Dismounted;
+        for (_, p) in entity.points.iter_mut().filter(|(i, _)| i.is_bosh()) {
+            p.location += Vector2D(0.0, -1.0);
+            p.previous_location += Vector2D(0.0, -1.0);
+        }
+        let track = rider_track(entity, vec![], |meta| meta.remount = true);
+
+        let mount_stretch = |frame: usize| {
+            let rider = &track.entity_positions_at(frame)[0];
+            rider
+                .bones
+                .iter()
+                .filter(|bone| matches!(bone.bone_type, BoneType::Mount { .. }))
+                .map(|bone| {
+                    let length = rider
+                        .point_at(bone.p1)
+                        .location
+                        .distance_squared(rider.point_at(bone.p2).location)
+                        .sqrt();
+                    (length - bone.resting_length).abs()
+                })
+                .fold(0.0, f64::max)
+        };
+
+        // the mount state advances after bones are applied, so mount bones are
+        // still ignored on the frame that the bosh starts remounting
+        let frames = 1 + REMOUNT_FRAMES as usize;
+        let stretches: Vec<f64> = (1..frames).map(mount_stretch).collect();
+        assert!(
+            stretches.windows(2).all(|pair| pair[1] < pair[0]),
+            "{stretches:?}"
+        );
+        assert_eq!(
+            MountState::Mounted,
+            track.entity_positions_at(frames)[0].mount_state
+        );
+        for frame in frames..frames + 20 {
+            assert!(mount_stretch(frame) < 0.15, "frame {frame}");
+        }
+    }
+
+    #[test]
+    fn track_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Track>();
+    }
+
+    #[test]
+    fn frames_can_be_read_concurrently() {
+        let track = floor_track();
+        let expected = floor_track().entity_positions_at(200);
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| track.entity_positions_at(200)))
+                .collect();
+
+            for handle in handles {
+                assert_eq!(expected, handle.join().unwrap());
+            }
+        });
+        assert_eq!(201, track.precomputed_frame_count());
+    }
+
+    #[test]
+    fn contact_events_report_lines_hit() {
+        let mut track = floor_track();
+        let unused = track.add_line(
+            Line::builder()
+                .point(5000.0, 5000.0)
+                .point(5100.0, 5000.0)
+                .build(),
+        );
+
+        let events = track.events_in(0..60);
+        let contacts: Vec<_> = events
+            .iter()
+            .filter_map(|e| match *e {
+                Event::Contact {
+                    frame,
+                    point,
+                    line,
+                    line_type,
+                    depth,
+                    ..
+                } => Some((frame, point, line, line_type, depth)),
+                _ => None,
+            })
+            .collect();
+
+        assert!(!contacts.is_empty());
+        assert!(contacts.iter().all(|c| c.2 == LineId(0)));
+        assert!(contacts
+            .iter()
+            .all(|c| c.3 == LineType::Normal && c.4 > 0.0));
+        assert!(!contacts.iter().any(|c| c.2 == unused));
+
+        let mut keys: Vec<_> = contacts.iter().map(|c| (c.0, c.1)).collect();
+        keys.dedup();
+        assert_eq!(contacts.len(), keys.len(), "duplicate contacts");
+
+        assert_eq!(events, track.events_in(0..60));
+        assert_eq!(
+            track.events_at(contacts[0].0),
+            events
+                .iter()
+                .copied()
+                .filter(|e| e.frame() == contacts[0].0)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn frame_after_with_events_matches_frame_after() {
+        let track = floor_track();
+        let riders = track.entity_positions_at(30);
+
+        assert_eq!(
+            frame_after(&riders, &track),
+            frame_after_with_events(&riders, &track, 31).0
+        );
+    }
+
+    #[test]
+    fn crash_reports_dismount_event() {
+        for (remount, policy) in [
+            (false, CachePolicy::Full),
+            (true, CachePolicy::Full),
+            (false, CachePolicy::Checkpoints { interval: 3 }),
+        ] {
+            let mut track = wall_crash_track(remount);
+            track.set_cache_policy(policy);
+
+            assert_eq!(Some(4), track.first_crash_frame(10), "{policy:?}");
+            assert_eq!(11, track.precomputed_frame_count());
+            assert_eq!(
+                MountState::Mounted,
+                track.entity_positions_at(3)[0].mount_state
+            );
+            if remount {
+                assert!(matches!(
+                    track.entity_positions_at(4)[0].mount_state,
+                    MountState::Dismounting(_)
+                ));
+            } else {
+                assert_eq!(2, track.entity_positions_at(4).len());
+            }
+
+            let dismounts: Vec<_> = track
+                .events_in(0..11)
+                .into_iter()
+                .filter(|e| matches!(e, Event::Dismount { .. }))
+                .collect();
+            assert!(
+                matches!(
+                    dismounts[..],
+                    [
+                        Event::Dismount {
+                            frame: 4,
+                            entity: EntityId(0),
+                            cause: DismountCause::MountBoneStretched(Bone {
+                                p1: PointIndex::SledPeg,
+                                p2: PointIndex::BoshButt,
+                                ..
+                            }),
+                        },
+                        ..
+                    ]
+                ),
+                "{dismounts:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn no_crash_on_flat_ground() {
+        assert_eq!(None, floor_track().first_crash_frame(200));
+    }
+
+    #[test]
+    fn stretched_mount_bone_reports_dismount() {
+        let mut entity = Entity::default_boshsled();
+        for (_, p) in entity.points.iter_mut().filter(|(i, _)| i.is_bosh()) {
+            p.location += Vector2D(0.0, -50.0);
+            p.previous_location += Vector2D(0.0, -50.0);
+        }
+        let track = Track::new(vec![entity], vec![]);
+
+        assert!(matches!(
+            track.events_at(1)[..],
+            [Event::Dismount {
+                frame: 1,
+                entity: EntityId(0),
+                cause: DismountCause::MountBoneStretched(Bone {
+                    bone_type: BoneType::Mount { .. },
+                    ..
+                }),
+            }]
+        ));
+    }
+
+    #[test]
+    fn entity_ids_persist_through_splits() {
+        let mut track = wall_crash_track(false);
+        let rider = track.entity_positions_at(0)[0].clone();
+        track.create_entity(rider);
+
+        let starting_ids: Vec<_> = track.entity_positions_at(0).iter().map(|e| e.id).collect();
+        assert_eq!(vec![EntityId(0), EntityId(1)], starting_ids);
+
+        let ids_and_parents = |frame| {
+            track
+                .entity_positions_at(frame)
+                .iter()
+                .map(|e| (e.id, e.parent))
+                .collect::<Vec<_>>()
+        };
+        let crash_frame = track.first_crash_frame(10).unwrap();
+        assert_eq!(
+            vec![(EntityId(0), None), (EntityId(1), None)],
+            ids_and_parents(crash_frame - 1)
+        );
+
+        let expected = vec![
+            (EntityId(2), Some(EntityId(0))),
+            (EntityId(3), Some(EntityId(0))),
+            (EntityId(4), Some(EntityId(1))),
+            (EntityId(5), Some(EntityId(1))),
+        ];
+        assert_eq!(expected, ids_and_parents(crash_frame));
+        assert_eq!(expected, ids_and_parents(crash_frame + 20));
+    }
+
+    #[test]
+    fn split_ids_wrap_around_the_largest_id() {
+        let mut riders = wall_crash_track(false).entity_positions_at(0);
+        riders[0].id = EntityId(u64::MAX - 1);
+        riders.push(Entity {
+            id: EntityId(u64::MAX),
+            ..riders[0].clone()
+        });
+        let track = Track::new(riders, wall_crash_track(false).all_lines().clone());
+
+        let crash_frame = track.first_crash_frame(10).unwrap();
+        let ids: Vec<_> = track
+            .entity_positions_at(crash_frame)
+            .iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(
+            vec![EntityId(0), EntityId(1), EntityId(2), EntityId(3)],
+            ids
+        );
+    }
+
+    #[test]
+    fn default_meta_matches_ez_physics() {
+        let track = floor_track();
+        let riders = track.entity_positions_at(20);
+
+        let ez: Vec<_> = riders
+            .iter()
+            .map(|e| e.clone().apply_all_physics_ez(&track).unwrap_same())
+            .collect();
+        assert_eq!(ez, track.entity_positions_at(21));
+    }
+
+    #[test]
+    fn meta_gravity_is_used() {
+        let fall = |gravity| {
+            let track = rider_track(Entity::default_boshsled(), vec![], |meta| {
+                meta.gravity = gravity
+            });
+            track.entity_positions_at(50)[0]
+                .point_at(PointIndex::SledTail)
+                .location
+        };
+
+        let normal = fall(Vector2D(0.0, 0.175));
+        let low = fall(Vector2D(0.0, 0.05));
+        assert!(low.1 < normal.1);
+
+        let sideways = fall(Vector2D(0.175, 0.0));
+        assert!((sideways.1 - fall(Vector2D(0.0, 0.0)).1).abs() < 1e-9);
+        assert!(sideways.0 > normal.0);
+    }
+
+    #[test]
+    fn frictionless_meta_keeps_speed() {
+        let mut bosh = Entity::default_bosh();
+        bosh.mutate_points(|p| {
+            p.previous_location -= Vector2D(2.0, 0.0);
+            p.momentum += Vector2D(2.0, 0.0);
+        });
+        let floor = Line::builder()
+            .point(-50.0, 10.0)
+            .point(1000.0, 10.0)
+            .build();
+        let sliding_bosh = || Track::new(vec![bosh.clone()], vec![floor]);
+
+        let mut track = sliding_bosh();
+        track.entity_positions_at(100);
+        let mut meta = track.meta.clone();
+        meta.friction_scale = 0.0;
+        track.set_meta(meta);
+        assert_eq!(1, track.precomputed_frame_count());
+
+        let speed = |track: &Track| avg_velocity(&track.entity_positions_at(100)[0]).0;
+        assert!(speed(&track) > speed(&sliding_bosh()));
+    }
+
+    #[test]
+    fn meta_iterations_are_used() {
+        let mut meta = TrackMeta {
+            iterations: 1,
+            ..Default::default()
+        };
+        let mut track = floor_track();
+        track.set_meta(meta.clone());
+        let one_iteration = track.entity_positions_at(50);
+
+        meta.iterations = 6;
+        track.set_meta(meta);
+        assert_ne!(one_iteration, track.entity_positions_at(50));
+        assert_eq!(
+            floor_track().entity_positions_at(50),
+            track.entity_positions_at(50)
+        );
+    }
+
+    #[test]
+    fn scarf_does_not_affect_rider() {
+        for track in [
+            floor_track(),
+            wall_crash_track(false),
+            wall_crash_track(true),
+        ] {
+            let mut riders = track.entity_positions_at(0);
+            riders.iter_mut().for_each(|r| r.scarf = None);
+            let without_scarf =
+                Track::new_with_meta(riders, track.all_lines().clone(), track.meta.clone());
+
+            for frame in 0..100 {
+                let mut riders = track.entity_positions_at(frame);
+                riders.iter_mut().for_each(|r| r.scarf = None);
+                assert_eq!(without_scarf.entity_positions_at(frame), riders);
+            }
+        }
+    }
+
+    #[test]
+    fn scarf_hangs_from_shoulder() {
+        let track = floor_track();
+
+        for frame in [1, 20, 80] {
+            let rider = &track.entity_positions_at(frame)[0];
+            let scarf = rider.scarf.as_ref().unwrap();
+
+            let mut previous = rider.point_at(PointIndex::BoshShoulder).location;
+            for p in &scarf.points {
+                let length = p.location.distance_squared(previous).sqrt();
+                assert!(length <= scarf.segment_length + 1e-9);
+                previous = p.location;
+            }
+        }
+
+        // trails behind a rider moving right
+        let rider = &track.entity_positions_at(80)[0];
+        let scarf = rider.scarf.as_ref().unwrap();
+        assert!(
+            scarf.points.last().unwrap().location.0
+                < rider.point_at(PointIndex::BoshShoulder).location.0
+        );
+    }
+
+    #[test]
+    fn scarf_chain_only_pulls_when_stretched() {
+        let mut rider = Entity::default_bosh();
+        rider.mutate_points(|p| p.previous_location = p.location);
+        let shoulder = rider.point_at(PointIndex::BoshShoulder).location;
+        let point_at = |offset: f64| EntityPoint {
+            previous_location: shoulder + Vector2D(offset, -1.0),
+            location: shoulder + Vector2D(offset, 0.0),
+            momentum: Vector2D(0.0, 1.0),
+            friction: 0.0,
+        };
+        rider.scarf = Some(Scarf {
+            anchor: PointIndex::BoshShoulder,
+            points: vec![point_at(-2.0), point_at(-4.0)].into(),
+            segment_length: 3.0,
+            air_friction: 0.5,
+        });
+        let gravity = Vector2D(0.0, 0.25);
+
+        // falls freely while slack: 0.5 of its last step, plus gravity
+        let mut slack = rider.clone();
+        slack.apply_scarf_physics(gravity);
+        let scarf = slack.scarf.unwrap();
+        assert_eq!(shoulder + Vector2D(-2.0, 0.75), scarf.points[0].location);
+        assert_eq!(shoulder + Vector2D(-4.0, 0.75), scarf.points[1].location);
+        assert_eq!(Vector2D(0.0, 0.75), scarf.points[0].momentum);
+
+        // a stretched link is pulled back to its resting length
+        let mut stretched = rider;
+        stretched.scarf.as_mut().unwrap().segment_length = 1.0;
+        stretched.apply_scarf_physics(gravity);
+        let scarf = stretched.scarf.unwrap();
+        let first = scarf.points[0].location;
+        assert!((first.distance_squared(shoulder).sqrt() - 1.0).abs() < 1e-12);
+        assert!((scarf.points[1].location.distance_squared(first).sqrt() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn long_scarves_behave_like_short_ones() {
+        let mut short = Entity::default_boshsled();
+        let mut long = short.clone();
+        let scarf = long.scarf.as_mut().unwrap();
+        let extra = (scarf.points.len()..2 * INLINE_SCARF_POINTS).map(|i| EntityPoint {
+            location: scarf.points[0].location - Vector2D(2.0 * i as f64, 0.0),
+            ..scarf.points[0]
+        });
+        scarf.points = scarf.points.iter().copied().chain(extra).collect();
+
+        let json = serde_json::to_string(&long).unwrap();
+        assert_eq!(long, serde_json::from_str::<Entity>(&json).unwrap());
+
+        let gravity = Vector2D(0.0, 0.175);
+        for _ in 0..20 {
+            short.apply_scarf_physics(gravity);
+            long.apply_scarf_physics(gravity);
+        }
+        let short = short.scarf.unwrap();
+        let long = long.scarf.unwrap();
+        assert_eq!(2 * INLINE_SCARF_POINTS, long.points.len());
+        assert_eq!(*short.points, long.points[..short.points.len()]);
+    }
+
+    #[test]
+    fn scarf_stays_with_bosh_after_crash() {
+        let track = wall_crash_track(false);
+        let riders = track.entity_positions_at(100);
+
+        assert_eq!(2, riders.len());
+        for rider in riders {
+            assert_eq!(rider.is_bosh(), rider.scarf.is_some());
+        }
+    }
+
+    #[test]
+    fn boshsled_skeleton_matches_default() {
+        let from_skeleton = Entity::from_skeleton(&Skeleton::boshsled()).unwrap();
+        let default = Entity::default_boshsled();
+
+        assert_eq!(default.points, from_skeleton.points);
+        assert_eq!(default.bones, from_skeleton.bones);
+        assert_eq!(default.joints, from_skeleton.joints);
+        assert_eq!(default.scarf, from_skeleton.scarf);
+        assert_eq!(default.break_groups, from_skeleton.break_groups);
+
+        // explicit break groups behave like the default bosh and sled groups
+        let mut riders = wall_crash_track(false).entity_positions_at(0);
+        let (bosh, sled) = riders[0].points.keys().partition(|index| index.is_bosh());
+        riders[0].break_groups = vec![bosh, sled].into();
+        let track = Track::new(riders, wall_crash_track(false).all_lines().clone());
+        for frame in [50, 100] {
+            let expected = wall_crash_track(false).entity_positions_at(frame);
+            let actual = track.entity_positions_at(frame);
+            assert_eq!(expected.len(), actual.len());
+            for (expected, actual) in expected.iter().zip(&actual) {
+                assert_eq!(expected.points, actual.points);
+            }
+        }
+    }
+
+    /// A triangle whose corners are held together by weak mount bones, each corner
+    /// in its own break group.
+    fn triangle_skeleton() -> Skeleton {
+        serde_json::from_str(
+            r#"{
+                "points": [
+                    { "index": "Custom(0)", "location": [0.0, 0.0], "friction": 0.5 },
+                    { "index": "Custom(1)", "location": [10.0, 0.0], "friction": 0.5 },
+                    { "index": "Custom(2)", "location": [5.0, -8.0] }
+                ],
+                "bones": [
+                    { "p1": "Custom(0)", "p2": "Custom(1)", "boneType": { "Mount": { "endurance": 0.01 } } },
+                    { "p1": "Custom(1)", "p2": "Custom(2)", "boneType": { "Mount": { "endurance": 0.01 } } },
+                    { "p1": "Custom(2)", "p2": "Custom(0)", "boneType": { "Mount": { "endurance": 0.01 } } }
+                ],
+                "breakGroups": [["Custom(0)"], ["Custom(1)"], ["Custom(2)"]]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn custom_skeleton_splits_into_break_groups() {
+        let mut triangle = Entity::from_skeleton(&triangle_skeleton()).unwrap();
+        triangle.mutate_points(|p| {
+            p.previous_location -= Vector2D(12.0, 0.0);
+            p.momentum += Vector2D(12.0, 0.0);
+        });
+        let track = Track::new(
+            vec![triangle],
+            vec![
+                Line::builder()
+                    .point(-50.0, 10.0)
+                    .point(300.0, 10.0)
+                    .build(),
+                Line::builder().point(60.0, 10.0).point(60.0, -20.0).build(),
+            ],
+        );
+
+        let riders = track.entity_positions_at(100);
+        assert_eq!(3, riders.len());
+        for (i, rider) in riders.iter().enumerate() {
+            assert_eq!(Some(EntityId(0)), rider.parent);
+            assert!(rider.points.contains_key(&PointIndex::Custom(i as u32)));
+            assert_eq!(1, rider.points.len());
+        }
+    }
+
+    #[test]
+    fn invalid_skeletons_are_rejected() {
+        let mut undefined_bone = triangle_skeleton();
+        undefined_bone.bones[0].p2 = PointIndex::Custom(7);
+        assert!(Entity::from_skeleton(&undefined_bone).is_err());
+
+        let mut duplicate_point = triangle_skeleton();
+        duplicate_point.points[1].index = PointIndex::Custom(0);
+        assert!(Entity::from_skeleton(&duplicate_point).is_err());
+
+        let mut ungrouped = triangle_skeleton();
+        ungrouped.break_groups.pop();
+        assert!(Entity::from_skeleton(&ungrouped).is_err());
+
+        let mut no_groups = triangle_skeleton();
+        no_groups.break_groups.clear();
+        let entity = Entity::from_skeleton(&no_groups).unwrap();
+        assert!(entity.break_groups.is_empty());
+        assert!(entity.joints.iter().all(|j| entity.is_group_joint(j)));
+    }
+
+    #[test]
+    fn missing_break_groups_mean_the_same_everywhere() {
+        // a bosh with a sled peg, but without groups or mount bones
+        let skeleton: Skeleton = serde_json::from_str(
+            r#"{
+                "points": [
+                    { "index": "BoshButt", "location": [0.0, 0.0] },
+                    { "index": "BoshShoulder", "location": [0.0, -10.0] },
+                    { "index": "SledPeg", "location": [0.0, 5.0] }
+                ],
+                "bones": [
+                    { "p1": "BoshButt", "p2": "BoshShoulder" },
+                    { "p1": "BoshButt", "p2": "SledPeg" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let entity = Entity::from_skeleton(&skeleton).unwrap();
+        let json = serde_json::to_value(&entity).unwrap();
+        assert!(json.get("breakGroups").is_none());
+        let reread: Entity = serde_json::from_value(json).unwrap();
+
+        for entity in [entity, reread] {
+            assert_eq!(0, entity.break_group_of(PointIndex::BoshButt));
+            assert_eq!(1, entity.break_group_of(PointIndex::SledPeg));
+            assert_eq!(2, entity.split().len());
+        }
+    }
+
+    #[test]
+    fn custom_entities_serialize() {
+        let entity = Entity::from_skeleton(&triangle_skeleton()).unwrap();
+        let json = serde_json::to_string(&entity).unwrap();
+
+        assert!(json.contains("\"Custom(2)\""));
+        assert_eq!(entity, serde_json::from_str(&json).unwrap());
+        assert!(serde_json::from_str::<PointIndex>("\"Custom(x)\"").is_err());
+        assert!(serde_json::from_str::<PointIndex>("\"BoshNose\"").is_err());
+    }
+
+    #[test]
+    fn boshsled_at_places_rider() {
+        let mut default = Entity::default_boshsled();
+        default.place(Vector2D(10.0, 20.0), Vector2D(1.0, -1.0));
+        assert_eq!(
+            default,
+            Entity::boshsled_at(Vector2D(10.0, 20.0), Vector2D(1.0, -1.0), 0.0)
+        );
+
+        let turned = Entity::boshsled_at(Vector2D(10.0, 20.0), Vector2D(1.0, -1.0), 2.0);
+        assert_eq!(
+            Vector2D(10.0, 20.0),
+            turned.point_at(PointIndex::SledPeg).location
+        );
+        for bone in turned.bones.iter() {
+            let length = turned
+                .point_at(bone.p1)
+                .location
+                .distance_squared(turned.point_at(bone.p2).location)
+                .sqrt();
+            assert!((length - bone.resting_length).abs() < 1e-9);
+        }
+        assert!(turned
+            .points
+            .values()
+            .all(|p| p.momentum == Vector2D(1.0, -1.0)));
+    }
+
+    #[test]
+    fn default_riders_share_bone_tables() {
+        use std::borrow::Cow;
+
+        let is_borrowed = |entity: &Entity| matches!(entity.bones, Cow::Borrowed(_));
+        assert!(is_borrowed(&Entity::default_boshsled()));
+        assert!(is_borrowed(
+            &Entity::from_skeleton(&Skeleton::boshsled()).unwrap()
+        ));
+        for part in wall_crash_track(false).entity_positions_at(100) {
+            assert!(is_borrowed(&part));
+        }
+    }
+
+    #[test]
+    fn point_map_is_ordered_by_index() {
+        let mut points = Entity::default_boshsled().points;
+        let point = points[&PointIndex::SledPeg];
+        points.insert(PointIndex::Custom(3), point);
+        points.insert(PointIndex::Custom(1), point);
+
+        let indices: Vec<_> = points.keys().copied().collect();
+        let mut sorted = indices.clone();
+        sorted.sort();
+        assert_eq!(sorted, indices);
+        assert_eq!(12, points.len());
+
+        assert_eq!(Some(point), points.remove(&PointIndex::Custom(1)));
+        assert!(!points.contains_key(&PointIndex::Custom(1)));
+        let json = serde_json::to_value(&points).unwrap();
+        assert!(json.is_object());
+        assert_eq!(points, serde_json::from_value(json).unwrap());
+    }
 }
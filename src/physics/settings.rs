@@ -0,0 +1,30 @@
+use std::collections::HashMap;
+
+use crate::game::{Line, LineId, LineType, TrackMeta, Vector2D};
+
+/// The settings used to simulate a single frame. They start out as the settings in a
+/// track's [`TrackMeta`], and can be changed partway through a track by triggers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameSettings {
+    pub gravity: Vector2D,
+    pub iterations: u64,
+    pub friction_scale: f64,
+    /// Lines which behave as if they had a different type.
+    pub line_types: HashMap<LineId, LineType>,
+}
+
+impl FrameSettings {
+    pub fn from_meta(meta: &TrackMeta) -> FrameSettings {
+        FrameSettings {
+            gravity: meta.gravity,
+            iterations: meta.iterations,
+            friction_scale: meta.friction_scale,
+            line_types: HashMap::new(),
+        }
+    }
+
+    /// Gets the type that a line behaves as.
+    pub fn line_type(&self, id: LineId, line: &Line) -> LineType {
+        self.line_types.get(&id).copied().unwrap_or(line.line_type)
+    }
+}
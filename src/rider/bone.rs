@@ -39,3 +39,13 @@ pub struct Joint {
     pub pair1: (PointIndex, PointIndex),
     pub pair2: (PointIndex, PointIndex),
 }
+
+impl Joint {
+    /// Returns true if the joint only checks points of the sled, meaning that the
+    /// sled itself breaks when the joint does.
+    pub fn is_sled_joint(&self) -> bool {
+        [self.pair1.0, self.pair1.1, self.pair2.0, self.pair2.1]
+            .iter()
+            .all(|p| !p.is_bosh())
+    }
+}
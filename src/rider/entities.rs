@@ -1,18 +1,96 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
 use crate::game::Vector2D;
 use crate::rider::bone::{Bone, BoneType};
-use crate::rider::point::{EntityPoint, PointIndex};
-use crate::rider::Joint;
+use crate::rider::point::{EntityPoint, PointIndex, PointMap};
+use crate::rider::{Joint, Scarf};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Entity {
-    pub points: HashMap<PointIndex, EntityPoint>,
+    /// Identifies the entity across frames. Entities in a track have unique ids.
+    #[serde(default)]
+    pub id: EntityId,
+    /// The entity that this entity was split from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<EntityId>,
+
+    pub points: PointMap,
+
+    /// Borrowed from a constant table for the default riders, so that cloning
+    /// an entity does not copy its bones.
+    pub bones: Cow<'static, [Bone]>,
+    pub joints: Cow<'static, [Joint]>,
+
+    #[serde(rename = "mountState", default)]
+    pub mount_state: MountState,
+
+    /// A scarf which is simulated along with the entity, but does not affect it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scarf: Option<Scarf>,
+
+    /// The groups of points which come apart when the entity breaks. If empty, the
+    /// points of the bosh and all other points are the two groups. This has the same
+    /// meaning as [`Skeleton::break_groups`](crate::rider::Skeleton::break_groups).
+    /// Shared between the frames of an entity, so that cloning it does not copy them.
+    #[serde(
+        rename = "breakGroups",
+        default,
+        skip_serializing_if = "<[_]>::is_empty"
+    )]
+    pub break_groups: Arc<[Vec<PointIndex>]>,
+}
+
+/// Identifies an entity. An entity keeps its id from frame to frame, and when it is
+/// split, both halves are given new ids and keep the original as their parent.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default,
+)]
+pub struct EntityId(pub u64);
+
+impl EntityId {
+    /// Returns an id which is greater than the id of every entity in `entities`, or
+    /// the smallest id that no entity uses if one of them has the id `u64::MAX`.
+    pub fn unused<'a, I>(entities: I) -> EntityId
+    where
+        I: IntoIterator<Item = &'a Entity>,
+        I::IntoIter: Clone,
+    {
+        let entities = entities.into_iter();
+        let Some(max) = entities.clone().map(|e| e.id.0).max() else {
+            return EntityId(0);
+        };
+        if let Some(id) = max.checked_add(1) {
+            return EntityId(id);
+        }
+
+        let used: HashSet<u64> = entities.map(|e| e.id.0).collect();
+        EntityId(
+            (0..)
+                .find(|id| !used.contains(id))
+                .expect("every entity id is taken"),
+        )
+    }
+}
 
-    pub bones: Vec<Bone>,
-    pub joints: Vec<Joint>,
+/// Whether the bosh of a boshsled is currently sitting on its sled. Only
+/// changes from `Mounted` when the track has remounting enabled, otherwise a
+/// boshsled is split into two entities when the bosh falls off.
+///
+/// The frame counters count how many frames the entity has been in that state.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum MountState {
+    #[default]
+    Mounted,
+    /// The bosh has just fallen off, and cannot remount yet.
+    Dismounting(u32),
+    /// The bosh will start remounting once it is close enough to the sled.
+    Dismounted,
+    /// The bosh is being pulled back onto the sled by weakened mount bones.
+    Remounting(u32),
 }
 
 impl Entity {
@@ -30,39 +108,75 @@ impl Entity {
             .unwrap_or_else(|| panic!("invalid index {index:?}"))
     }
 
-    /// Utility function for applying a mapping to all points of the entity
-    pub fn mutate_points<F: FnMut(&mut EntityPoint)>(&mut self, mapper: F) {
-        self.points.values_mut().for_each(mapper);
+    /// Utility function for applying a mapping to all points of the entity,
+    /// including the points of its scarf
+    pub fn mutate_points<F: FnMut(&mut EntityPoint)>(&mut self, mut mapper: F) {
+        self.points.values_mut().for_each(&mut mapper);
+        if let Some(scarf) = &mut self.scarf {
+            scarf.points.iter_mut().for_each(mapper);
+        }
+    }
+
+    /// Moves the entity by `position`, and sets the velocity of every point,
+    /// including the points of its scarf, to `velocity`.
+    pub fn place(&mut self, position: Vector2D, velocity: Vector2D) {
+        self.mutate_points(|p| {
+            p.location += position;
+            p.previous_location = p.location - velocity;
+            p.momentum = velocity;
+        });
     }
 
     pub fn default_boshsled() -> Entity {
         let points = boshsled::default_points();
-        let bones = boshsled::default_bones(&points);
-        let joints = boshsled::default_joints();
+        let scarf = bosh::default_scarf(&points);
         Entity {
+            id: Default::default(),
+            parent: None,
             points,
-            bones,
-            joints,
+            bones: Cow::Borrowed(&boshsled::DEFAULT_BONES),
+            joints: Cow::Borrowed(&boshsled::DEFAULT_JOINTS),
+            mount_state: Default::default(),
+            scarf: Some(scarf),
+            break_groups: Default::default(),
         }
     }
 
+    /// Creates a default boshsled whose sled peg sits on `position`, moving at
+    /// `velocity`, and turned by `rotation` radians around its sled peg.
+    pub fn boshsled_at(position: Vector2D, velocity: Vector2D, rotation: f64) -> Entity {
+        let mut rider = Entity::default_boshsled();
+        rider.mutate_points(|p| p.location = p.location.rotate_rad(rotation));
+        rider.place(position, velocity);
+
+        rider
+    }
+
     pub fn default_bosh() -> Entity {
         let points = bosh::default_points();
-        let bones = bosh::default_bones(&points);
+        let scarf = bosh::default_scarf(&points);
         Entity {
+            id: Default::default(),
+            parent: None,
             points,
-            bones,
+            bones: Cow::Borrowed(&bosh::DEFAULT_BONES),
             joints: Default::default(),
+            mount_state: Default::default(),
+            scarf: Some(scarf),
+            break_groups: Default::default(),
         }
     }
 
     pub fn default_sled() -> Entity {
-        let points = sled::default_points();
-        let bones = sled::default_bones(&points);
         Entity {
-            points,
-            bones,
+            id: Default::default(),
+            parent: None,
+            points: sled::default_points(),
+            bones: Cow::Borrowed(&sled::DEFAULT_BONES),
             joints: Default::default(),
+            mount_state: Default::default(),
+            scarf: None,
+            break_groups: Default::default(),
         }
     }
 
@@ -78,32 +192,79 @@ impl Entity {
         !self.is_bosh() && !self.is_sled()
     }
 
-    /// Splits a boshsled into a bosh and a sled
-    pub fn split(self) -> (Entity, Entity) {
-        let (bosh_points, sled_points) = self
-            .points
-            .into_iter()
-            .partition(|(index, _)| index.is_bosh());
+    /// Returns the position in [`Entity::break_groups`] of the group that a point
+    /// belongs to. Points which are not in any group are treated as one more group.
+    pub fn break_group_of(&self, index: PointIndex) -> usize {
+        if self.break_groups.is_empty() {
+            return if index.is_bosh() { 0 } else { 1 };
+        }
 
-        let (bosh_bones, sled_bones) = self
-            .bones
-            .into_iter()
-            .filter(|bone| !matches!(bone.bone_type, BoneType::Mount { .. }))
-            .filter(|bone| bone.is_bosh_bone() || bone.is_sled_bone())
-            .partition(|bone| bone.is_bosh_bone());
-
-        (
-            Entity {
-                points: bosh_points,
-                bones: bosh_bones,
-                joints: vec![],
-            },
-            Entity {
-                points: sled_points,
-                bones: sled_bones,
-                joints: vec![],
-            },
-        )
+        self.break_groups
+            .iter()
+            .position(|group| group.contains(&index))
+            .unwrap_or(self.break_groups.len())
+    }
+
+    /// Returns true if every point that `joint` checks is in the same break group,
+    /// meaning that the group itself breaks when the joint does.
+    pub fn is_group_joint(&self, joint: &Joint) -> bool {
+        let group = self.break_group_of(joint.pair1.0);
+        [joint.pair1.1, joint.pair2.0, joint.pair2.1]
+            .iter()
+            .all(|&p| self.break_group_of(p) == group)
+    }
+
+    /// Splits an entity into one entity per break group, in the order of the groups.
+    /// For a boshsled, this is a bosh followed by a sled. Every part has `self` as its
+    /// parent, and keeps the id of `self` until it is given a new one.
+    ///
+    /// Mount bones, bones between groups and joints are dropped, so the parts never
+    /// break any further.
+    pub fn split(self) -> Vec<Entity> {
+        let group_count = match self.break_groups.len() {
+            0 => 2,
+            n => n + 1,
+        };
+        let mut parts: Vec<Entity> = (0..group_count)
+            .map(|_| Entity {
+                id: self.id,
+                parent: Some(self.id),
+                points: PointMap::new(),
+                bones: Cow::Owned(vec![]),
+                joints: Cow::Owned(vec![]),
+                mount_state: MountState::Mounted,
+                scarf: None,
+                break_groups: Default::default(),
+            })
+            .collect();
+
+        for (index, point) in self.points.iter() {
+            parts[self.break_group_of(*index)]
+                .points
+                .insert(*index, *point);
+        }
+        for bone in self.bones.iter() {
+            let group = self.break_group_of(bone.p1);
+            if !matches!(bone.bone_type, BoneType::Mount { .. })
+                && group == self.break_group_of(bone.p2)
+            {
+                parts[group].bones.to_mut().push(*bone);
+            }
+        }
+        // the scarf stays with whichever part it hangs from
+        if let Some(anchor) = self.scarf.as_ref().map(|scarf| scarf.anchor) {
+            let group = self.break_group_of(anchor);
+            parts[group].scarf = self.scarf;
+        }
+
+        // the parts have no mount bones or joints left, so they keep the default
+        // groups as they can never be split again
+        parts.retain(|part| !part.points.is_empty());
+        for part in &mut parts {
+            part.bones = shared_bones(std::mem::take(&mut part.bones).into_owned());
+        }
+
+        parts
     }
 }
 
@@ -117,107 +278,96 @@ pub mod boshsled {
         + DEFAULT_SLED_MOUNT_BONES
         + DEFAULT_BOSH_MOUNT_BONES;
 
-    pub fn default_points() -> HashMap<PointIndex, EntityPoint> {
+    /// How much the mount bones of the default boshsled can stretch before breaking.
+    pub const MOUNT_ENDURANCE: f64 = 0.057;
+    const MOUNT: BoneType = BoneType::Mount {
+        endurance: MOUNT_ENDURANCE,
+    };
+
+    pub fn default_points() -> PointMap {
         sled::default_points()
             .into_iter()
             .chain(bosh::default_points())
             .collect()
     }
 
-    pub fn default_bones(points: &HashMap<PointIndex, EntityPoint>) -> Vec<Bone> {
-        // insert mounter bones in between regular and repel bones
-        let (bosh_normal_bones, bosh_repel_bones) = bosh::default_bones(points)
-            .iter()
-            .partition(|bone| matches!(bone.bone_type, BoneType::Normal));
-
-        vec![
-            sled::default_bones(points),
-            default_sled_mounter_bones(points),
-            bosh_normal_bones,
-            default_bosh_mounter_bones(points),
-            bosh_repel_bones,
-        ]
-        .concat()
-    }
-
-    // TODO - precompute resting lengths of bones
-    pub fn default_sled_mounter_bones(points: &HashMap<PointIndex, EntityPoint>) -> Vec<Bone> {
-        make_bones(
-            vec![
-                (
-                    PointIndex::SledPeg,
-                    PointIndex::BoshButt,
-                    BoneType::Mount { endurance: 0.057 },
-                ),
-                (
-                    PointIndex::SledTail,
-                    PointIndex::BoshButt,
-                    BoneType::Mount { endurance: 0.057 },
-                ),
-                (
-                    PointIndex::SledNose,
-                    PointIndex::BoshButt,
-                    BoneType::Mount { endurance: 0.057 },
-                ),
-            ],
-            points,
-        )
-    }
-
-    pub fn default_joints() -> Vec<Joint> {
-        vec![
-            Joint {
-                pair1: (PointIndex::BoshShoulder, PointIndex::BoshButt),
-                pair2: (PointIndex::SledRope, PointIndex::SledPeg),
-            },
-            Joint {
-                pair1: (PointIndex::SledPeg, PointIndex::SledTail),
-                pair2: (PointIndex::SledRope, PointIndex::SledPeg),
-            },
-        ]
-    }
-
-    // TODO - precompute resting lengths of bones
-    fn default_bosh_mounter_bones(points: &HashMap<PointIndex, EntityPoint>) -> Vec<Bone> {
-        make_bones(
-            vec![
-                (
-                    PointIndex::BoshShoulder,
-                    PointIndex::SledPeg,
-                    BoneType::Mount { endurance: 0.057 },
-                ),
-                (
-                    PointIndex::SledRope,
-                    PointIndex::BoshLeftHand,
-                    BoneType::Mount { endurance: 0.057 },
-                ),
-                (
-                    PointIndex::SledRope,
-                    PointIndex::BoshRightHand,
-                    BoneType::Mount { endurance: 0.057 },
-                ),
-                (
-                    PointIndex::BoshLeftFoot,
-                    PointIndex::SledNose,
-                    BoneType::Mount { endurance: 0.057 },
-                ),
-                (
-                    PointIndex::BoshRightFoot,
-                    PointIndex::SledNose,
-                    BoneType::Mount { endurance: 0.057 },
-                ),
-            ],
-            points,
-        )
-    }
+    /// The sled's bones, then the sled's mount bones, the bosh's normal bones, the
+    /// bosh's mount bones and finally the bosh's repel bones. Built from
+    /// [`sled::DEFAULT_BONES`] and [`bosh::DEFAULT_BONES`], so the tables cannot drift
+    /// apart.
+    pub const DEFAULT_BONES: [Bone; DEFAULT_BONE_COUNT] = {
+        let (bosh_normal, bosh_repel) =
+            bosh::DEFAULT_BONES.split_at(bosh::DEFAULT_BONE_COUNT - bosh::DEFAULT_REPEL_BONE_COUNT);
+        concat_bones(&[
+            &sled::DEFAULT_BONES,
+            &SLED_MOUNT_BONES,
+            bosh_normal,
+            &BOSH_MOUNT_BONES,
+            bosh_repel,
+        ])
+    };
+
+    /// The mount bones which hold the sled under the bosh's butt.
+    const SLED_MOUNT_BONES: [Bone; DEFAULT_SLED_MOUNT_BONES] = [
+        bone(PointIndex::SledPeg, PointIndex::BoshButt, 5.0, MOUNT),
+        bone(
+            PointIndex::SledTail,
+            PointIndex::BoshButt,
+            7.0710678118654755,
+            MOUNT,
+        ),
+        bone(
+            PointIndex::SledNose,
+            PointIndex::BoshButt,
+            11.180339887498949,
+            MOUNT,
+        ),
+    ];
+
+    /// The mount bones which hold the bosh's hands and feet on the sled.
+    const BOSH_MOUNT_BONES: [Bone; DEFAULT_BOSH_MOUNT_BONES] = [
+        bone(
+            PointIndex::BoshShoulder,
+            PointIndex::SledPeg,
+            7.433034373659253,
+            MOUNT,
+        ),
+        bone(
+            PointIndex::SledRope,
+            PointIndex::BoshLeftHand,
+            7.810249675906654,
+            MOUNT,
+        ),
+        bone(
+            PointIndex::SledRope,
+            PointIndex::BoshRightHand,
+            7.810249675906654,
+            MOUNT,
+        ),
+        bone(PointIndex::BoshLeftFoot, PointIndex::SledNose, 5.0, MOUNT),
+        bone(PointIndex::BoshRightFoot, PointIndex::SledNose, 5.0, MOUNT),
+    ];
+
+    pub const DEFAULT_JOINTS: [Joint; 2] = [
+        Joint {
+            pair1: (PointIndex::BoshShoulder, PointIndex::BoshButt),
+            pair2: (PointIndex::SledRope, PointIndex::SledPeg),
+        },
+        Joint {
+            pair1: (PointIndex::SledPeg, PointIndex::SledTail),
+            pair2: (PointIndex::SledRope, PointIndex::SledPeg),
+        },
+    ];
 }
 
 pub mod bosh {
     use super::*;
 
     pub const DEFAULT_BONE_COUNT: usize = 8;
+    /// How many of the bosh's bones, at the end of [`DEFAULT_BONES`], are repel bones.
+    pub const DEFAULT_REPEL_BONE_COUNT: usize = 2;
 
-    pub fn default_points() -> HashMap<PointIndex, EntityPoint> {
+    pub fn default_points() -> PointMap {
         let left_foot = make_entity_point(Vector2D(10.0, 5.0), 0.0);
         let right_foot = make_entity_point(Vector2D(10.0, 5.0), 0.0);
         let left_hand = make_entity_point(Vector2D(11.5, -5.0), 0.1);
@@ -225,7 +375,7 @@ pub mod bosh {
         let shoulder = make_entity_point(Vector2D(5.0, -5.5), 0.8);
         let butt = make_entity_point(Vector2D(5.0, 0.0), 0.8);
 
-        HashMap::from([
+        PointMap::from([
             (PointIndex::BoshLeftFoot, left_foot),
             (PointIndex::BoshRightFoot, right_foot),
             (PointIndex::BoshLeftHand, left_hand),
@@ -235,68 +385,93 @@ pub mod bosh {
         ])
     }
 
-    // TODO - precompute resting lengths of bones
-    pub fn default_bones(points: &HashMap<PointIndex, EntityPoint>) -> Vec<Bone> {
-        make_bones(
-            vec![
-                (
-                    PointIndex::BoshShoulder,
-                    PointIndex::BoshButt,
-                    BoneType::Normal,
-                ),
-                (
-                    PointIndex::BoshShoulder,
-                    PointIndex::BoshLeftHand,
-                    BoneType::Normal,
-                ),
-                (
-                    PointIndex::BoshShoulder,
-                    PointIndex::BoshRightHand,
-                    BoneType::Normal,
-                ),
-                (
-                    PointIndex::BoshButt,
-                    PointIndex::BoshLeftFoot,
-                    BoneType::Normal,
-                ),
-                (
-                    PointIndex::BoshButt,
-                    PointIndex::BoshRightFoot,
-                    BoneType::Normal,
-                ),
-                (
-                    PointIndex::BoshShoulder,
-                    PointIndex::BoshRightHand,
-                    BoneType::Normal,
-                ),
-                (
-                    PointIndex::BoshShoulder,
-                    PointIndex::BoshLeftFoot,
-                    BoneType::Repel { length_factor: 0.5 },
-                ),
-                (
-                    PointIndex::BoshShoulder,
-                    PointIndex::BoshRightFoot,
-                    BoneType::Repel { length_factor: 0.5 },
-                ),
-            ],
-            points,
-        )
+    /// The bosh's normal bones, then its repel bones.
+    pub const DEFAULT_BONES: [Bone; DEFAULT_BONE_COUNT] = [
+        bone(
+            PointIndex::BoshShoulder,
+            PointIndex::BoshButt,
+            5.5,
+            BoneType::Normal,
+        ),
+        bone(
+            PointIndex::BoshShoulder,
+            PointIndex::BoshLeftHand,
+            6.519202405202649,
+            BoneType::Normal,
+        ),
+        bone(
+            PointIndex::BoshShoulder,
+            PointIndex::BoshRightHand,
+            6.519202405202649,
+            BoneType::Normal,
+        ),
+        bone(
+            PointIndex::BoshButt,
+            PointIndex::BoshLeftFoot,
+            7.0710678118654755,
+            BoneType::Normal,
+        ),
+        bone(
+            PointIndex::BoshButt,
+            PointIndex::BoshRightFoot,
+            7.0710678118654755,
+            BoneType::Normal,
+        ),
+        bone(
+            PointIndex::BoshShoulder,
+            PointIndex::BoshRightHand,
+            6.519202405202649,
+            BoneType::Normal,
+        ),
+        bone(
+            PointIndex::BoshShoulder,
+            PointIndex::BoshLeftFoot,
+            11.629703349613008,
+            BoneType::Repel { length_factor: 0.5 },
+        ),
+        bone(
+            PointIndex::BoshShoulder,
+            PointIndex::BoshRightFoot,
+            11.629703349613008,
+            BoneType::Repel { length_factor: 0.5 },
+        ),
+    ];
+
+    /// The number of points in the scarf, not counting the shoulder it hangs from.
+    pub const DEFAULT_SCARF_POINTS: usize = 6;
+    pub const DEFAULT_SCARF_SEGMENT_LENGTH: f64 = 2.0;
+    pub const DEFAULT_SCARF_AIR_FRICTION: f64 = 0.9;
+
+    /// A scarf which trails straight back from the shoulder.
+    pub fn default_scarf(points: &PointMap) -> Scarf {
+        let shoulder = points[&PointIndex::BoshShoulder].location;
+
+        Scarf {
+            anchor: PointIndex::BoshShoulder,
+            points: (1..=DEFAULT_SCARF_POINTS)
+                .map(|i| {
+                    let offset = Vector2D(DEFAULT_SCARF_SEGMENT_LENGTH * i as f64, 0.0);
+                    make_entity_point(shoulder - offset, 0.0)
+                })
+                .collect(),
+            segment_length: DEFAULT_SCARF_SEGMENT_LENGTH,
+            air_friction: DEFAULT_SCARF_AIR_FRICTION,
+        }
     }
 }
 
-mod sled {
+pub mod sled {
     use super::*;
 
     pub const DEFAULT_BONE_COUNT: usize = 6;
 
-    pub fn default_points() -> HashMap<PointIndex, EntityPoint> {
+    pub fn default_points() -> PointMap {
         let peg = make_entity_point(Vector2D(0.0, 0.0), 0.8);
         let nose = make_entity_point(Vector2D(15.0, 5.0), 0.0);
         let tail = make_entity_point(Vector2D(0.0, 5.0), 0.0);
         let rope = make_entity_point(Vector2D(17.5, 0.0), 0.0);
 
-        HashMap::from([
+        PointMap::from([
             (PointIndex::SledPeg, peg),
             (PointIndex::SledNose, nose),
             (PointIndex::SledTail, tail),
@@ -304,24 +479,63 @@ mod sled {
         ])
     }
 
-    pub fn default_bones(points: &HashMap<PointIndex, EntityPoint>) -> Vec<Bone> {
-        make_bones(
-            vec![
-                (PointIndex::SledPeg, PointIndex::SledTail, BoneType::Normal),
-                (PointIndex::SledTail, PointIndex::SledNose, BoneType::Normal),
-                (PointIndex::SledNose, PointIndex::SledRope, BoneType::Normal),
-                (PointIndex::SledRope, PointIndex::SledPeg, BoneType::Normal),
-                (PointIndex::SledPeg, PointIndex::SledNose, BoneType::Normal),
-                (PointIndex::SledRope, PointIndex::SledTail, BoneType::Normal),
-            ],
-            points,
-        )
-    }
+    /// The sled's bones, which are all normal bones.
+    pub const DEFAULT_BONES: [Bone; DEFAULT_BONE_COUNT] = [
+        bone(
+            PointIndex::SledPeg,
+            PointIndex::SledTail,
+            5.0,
+            BoneType::Normal,
+        ),
+        bone(
+            PointIndex::SledTail,
+            PointIndex::SledNose,
+            15.0,
+            BoneType::Normal,
+        ),
+        bone(
+            PointIndex::SledNose,
+            PointIndex::SledRope,
+            5.5901699437494745,
+            BoneType::Normal,
+        ),
+        bone(
+            PointIndex::SledRope,
+            PointIndex::SledPeg,
+            17.5,
+            BoneType::Normal,
+        ),
+        bone(
+            PointIndex::SledPeg,
+            PointIndex::SledNose,
+            15.811388300841896,
+            BoneType::Normal,
+        ),
+        bone(
+            PointIndex::SledRope,
+            PointIndex::SledTail,
+            18.200274723201296,
+            BoneType::Normal,
+        ),
+    ];
 }
 
 // ==== PRIVATE UTIL FUNCTIONS ====
 
-fn make_entity_point(loc: Vector2D, friction: f64) -> EntityPoint {
+/// Borrows the constant table of a default rider if `bones` is the same as it, so
+/// that entities built from data or split from a default boshsled clone cheaply.
+pub(crate) fn shared_bones(bones: Vec<Bone>) -> Cow<'static, [Bone]> {
+    [
+        &boshsled::DEFAULT_BONES[..],
+        &bosh::DEFAULT_BONES[..],
+        &sled::DEFAULT_BONES[..],
+    ]
+    .into_iter()
+    .find(|&table| bones == table)
+    .map_or(Cow::Owned(bones), Cow::Borrowed)
+}
+
+pub(crate) fn make_entity_point(loc: Vector2D, friction: f64) -> EntityPoint {
     EntityPoint {
         previous_location: loc - Vector2D(0.4, 0.0),
         momentum: Vector2D(0.4, 0.0),
@@ -330,27 +544,37 @@ fn make_entity_point(loc: Vector2D, friction: f64) -> EntityPoint {
     }
 }
 
-fn make_bones(
-    bones: Vec<(PointIndex, PointIndex, BoneType)>,
-    point_map: &HashMap<PointIndex, EntityPoint>,
-) -> Vec<Bone> {
+/// Joins bone tables into one, in order. Fails to compile if the tables do not add
+/// up to exactly `N` bones.
+const fn concat_bones<const N: usize>(tables: &[&[Bone]]) -> [Bone; N] {
+    let mut bones = [bone(
+        PointIndex::SledPeg,
+        PointIndex::SledPeg,
+        0.0,
+        BoneType::Normal,
+    ); N];
+    let mut len = 0;
+    let mut table = 0;
+    while table < tables.len() {
+        let mut i = 0;
+        while i < tables[table].len() {
+            bones[len] = tables[table][i];
+            len += 1;
+            i += 1;
+        }
+        table += 1;
+    }
+    assert!(len == N, "bone tables do not add up to the table size");
+
     bones
-        .iter()
-        .map(|(p1, p2, bone_type)| Bone {
-            p1: *p1,
-            p2: *p2,
-            resting_length: length_between(p1, p2, point_map),
-            bone_type: *bone_type,
-        })
-        .collect()
 }
 
-fn length_between(
-    p1: &PointIndex,
-    p2: &PointIndex,
-    point_map: &HashMap<PointIndex, EntityPoint>,
-) -> f64 {
-    (point_map.get(p2).expect("").location - point_map.get(p1).expect("").location)
-        .length_squared()
-        .sqrt()
+/// Bone tables list resting lengths precomputed from the default points.
+const fn bone(p1: PointIndex, p2: PointIndex, resting_length: f64, bone_type: BoneType) -> Bone {
+    Bone {
+        p1,
+        p2,
+        resting_length,
+        bone_type,
+    }
 }
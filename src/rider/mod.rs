@@ -1,7 +1,13 @@
 mod bone;
 mod entities;
 mod point;
+mod presets;
+mod scarf;
+mod skeleton;
 
 pub use bone::*;
 pub use entities::*;
 pub use point::*;
+pub use presets::*;
+pub use scarf::*;
+pub use skeleton::*;
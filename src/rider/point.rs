@@ -1,7 +1,13 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Index;
+use std::{array, iter, slice, vec};
+
 use crate::Vector2D;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Serialize, Deserialize, Hash, Debug, PartialEq, Eq, Ord, PartialOrd, Copy, Clone)]
+#[serde(into = "String", try_from = "String")]
 pub enum PointIndex {
     BoshLeftFoot,
     BoshRightFoot,
@@ -14,12 +20,82 @@ pub enum PointIndex {
     SledTail,
     SledNose,
     SledRope,
+
+    /// A point of a custom [`Skeleton`](crate::rider::Skeleton). Written as
+    /// `"Custom(n)"` when serialized.
+    Custom(u32),
 }
 
+/// The number of points which have a name in [`PointIndex`].
+pub const NAMED_POINT_COUNT: usize = 10;
+
 impl PointIndex {
+    const NAMED: [PointIndex; NAMED_POINT_COUNT] = [
+        PointIndex::BoshLeftFoot,
+        PointIndex::BoshRightFoot,
+        PointIndex::BoshLeftHand,
+        PointIndex::BoshRightHand,
+        PointIndex::BoshShoulder,
+        PointIndex::BoshButt,
+        PointIndex::SledPeg,
+        PointIndex::SledTail,
+        PointIndex::SledNose,
+        PointIndex::SledRope,
+    ];
+
     pub fn is_bosh(&self) -> bool {
         &PointIndex::BoshLeftFoot <= self && self <= &PointIndex::BoshButt
     }
+
+    /// The position of a named point in [`PointIndex::NAMED`].
+    fn named_slot(self) -> Option<usize> {
+        Some(match self {
+            PointIndex::BoshLeftFoot => 0,
+            PointIndex::BoshRightFoot => 1,
+            PointIndex::BoshLeftHand => 2,
+            PointIndex::BoshRightHand => 3,
+            PointIndex::BoshShoulder => 4,
+            PointIndex::BoshButt => 5,
+            PointIndex::SledPeg => 6,
+            PointIndex::SledTail => 7,
+            PointIndex::SledNose => 8,
+            PointIndex::SledRope => 9,
+            PointIndex::Custom(_) => return None,
+        })
+    }
+}
+
+impl fmt::Display for PointIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl From<PointIndex> for String {
+    fn from(index: PointIndex) -> String {
+        index.to_string()
+    }
+}
+
+impl TryFrom<String> for PointIndex {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<PointIndex, String> {
+        if let Some(n) = name
+            .strip_prefix("Custom(")
+            .and_then(|rest| rest.strip_suffix(')'))
+        {
+            return n
+                .parse()
+                .map(PointIndex::Custom)
+                .map_err(|_| format!("invalid custom point index {name:?}"));
+        }
+
+        PointIndex::NAMED
+            .into_iter()
+            .find(|index| index.to_string() == name)
+            .ok_or_else(|| format!("unknown point index {name:?}"))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
@@ -30,3 +106,213 @@ pub struct EntityPoint {
     pub momentum: Vector2D,
     pub friction: f64,
 }
+
+/// The points of an entity, ordered by their index.
+///
+/// Named points are kept in a fixed-size array, so entities made only of named
+/// points can be cloned without allocating. Serialized as a map from index to point.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct PointMap {
+    named: [Option<EntityPoint>; NAMED_POINT_COUNT],
+    /// Sorted by index.
+    custom: Vec<(PointIndex, EntityPoint)>,
+}
+
+impl PointMap {
+    pub fn new() -> PointMap {
+        PointMap::default()
+    }
+
+    pub fn get(&self, index: &PointIndex) -> Option<&EntityPoint> {
+        match index.named_slot() {
+            Some(slot) => self.named[slot].as_ref(),
+            None => self.custom_position(index).ok().map(|i| &self.custom[i].1),
+        }
+    }
+
+    pub fn get_mut(&mut self, index: &PointIndex) -> Option<&mut EntityPoint> {
+        match index.named_slot() {
+            Some(slot) => self.named[slot].as_mut(),
+            None => {
+                let i = self.custom_position(index).ok()?;
+                Some(&mut self.custom[i].1)
+            }
+        }
+    }
+
+    pub fn contains_key(&self, index: &PointIndex) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// Adds a point, returning the point which was previously at `index`.
+    pub fn insert(&mut self, index: PointIndex, point: EntityPoint) -> Option<EntityPoint> {
+        match index.named_slot() {
+            Some(slot) => self.named[slot].replace(point),
+            None => match self.custom_position(&index) {
+                Ok(i) => Some(std::mem::replace(&mut self.custom[i].1, point)),
+                Err(i) => {
+                    self.custom.insert(i, (index, point));
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn remove(&mut self, index: &PointIndex) -> Option<EntityPoint> {
+        match index.named_slot() {
+            Some(slot) => self.named[slot].take(),
+            None => {
+                let i = self.custom_position(index).ok()?;
+                Some(self.custom.remove(i).1)
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.named.iter().flatten().count() + self.custom.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> PointMapIter<'_> {
+        PointMapIter {
+            named: PointIndex::NAMED.iter().zip(&self.named),
+            custom: self.custom.iter(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&PointIndex, &mut EntityPoint)> {
+        let named = PointIndex::NAMED.iter().zip(&mut self.named);
+        named
+            .filter_map(|(index, point)| Some((index, point.as_mut()?)))
+            .chain(
+                self.custom
+                    .iter_mut()
+                    .map(|(index, point)| (&*index, point)),
+            )
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &PointIndex> {
+        self.iter().map(|(index, _)| index)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &EntityPoint> {
+        self.iter().map(|(_, point)| point)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut EntityPoint> {
+        self.iter_mut().map(|(_, point)| point)
+    }
+
+    fn custom_position(&self, index: &PointIndex) -> Result<usize, usize> {
+        self.custom.binary_search_by_key(index, |(i, _)| *i)
+    }
+}
+
+impl Index<&PointIndex> for PointMap {
+    type Output = EntityPoint;
+
+    fn index(&self, index: &PointIndex) -> &EntityPoint {
+        self.get(index)
+            .unwrap_or_else(|| panic!("invalid index {index:?}"))
+    }
+}
+
+impl FromIterator<(PointIndex, EntityPoint)> for PointMap {
+    fn from_iter<T: IntoIterator<Item = (PointIndex, EntityPoint)>>(iter: T) -> PointMap {
+        let mut points = PointMap::new();
+        for (index, point) in iter {
+            points.insert(index, point);
+        }
+
+        points
+    }
+}
+
+impl<const N: usize> From<[(PointIndex, EntityPoint); N]> for PointMap {
+    fn from(points: [(PointIndex, EntityPoint); N]) -> PointMap {
+        points.into_iter().collect()
+    }
+}
+
+impl IntoIterator for PointMap {
+    type Item = (PointIndex, EntityPoint);
+    type IntoIter = PointMapIntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        PointMapIntoIter {
+            named: PointIndex::NAMED.into_iter().zip(self.named),
+            custom: self.custom.into_iter(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a PointMap {
+    type Item = (&'a PointIndex, &'a EntityPoint);
+    type IntoIter = PointMapIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the points of a [`PointMap`], ordered by their index.
+#[derive(Clone, Debug)]
+pub struct PointMapIter<'a> {
+    named: iter::Zip<slice::Iter<'a, PointIndex>, slice::Iter<'a, Option<EntityPoint>>>,
+    custom: slice::Iter<'a, (PointIndex, EntityPoint)>,
+}
+
+impl<'a> Iterator for PointMapIter<'a> {
+    type Item = (&'a PointIndex, &'a EntityPoint);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (index, point) in self.named.by_ref() {
+            if let Some(point) = point {
+                return Some((index, point));
+            }
+        }
+        self.custom.next().map(|(index, point)| (index, point))
+    }
+}
+
+/// An owning iterator over the points of a [`PointMap`], ordered by their index.
+#[derive(Debug)]
+pub struct PointMapIntoIter {
+    named: iter::Zip<
+        array::IntoIter<PointIndex, NAMED_POINT_COUNT>,
+        array::IntoIter<Option<EntityPoint>, NAMED_POINT_COUNT>,
+    >,
+    custom: vec::IntoIter<(PointIndex, EntityPoint)>,
+}
+
+impl Iterator for PointMapIntoIter {
+    type Item = (PointIndex, EntityPoint);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (index, point) in self.named.by_ref() {
+            if let Some(point) = point {
+                return Some((index, point));
+            }
+        }
+        self.custom.next()
+    }
+}
+
+impl Serialize for PointMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for PointMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(
+            BTreeMap::<PointIndex, EntityPoint>::deserialize(deserializer)?
+                .into_iter()
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game::Vector2D;
+use crate::rider::{BoneType, Entity, Skeleton};
+
+/// The size of the tiny rider, as a fraction of the size of the default boshsled.
+pub const TINY_RIDER_SCALE: f64 = 0.5;
+/// How strongly the points of the grippy sled grip lines.
+pub const GRIPPY_SLED_FRICTION: f64 = 0.8;
+
+/// The riders that can be spawned without describing a [`Skeleton`] by hand.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum RiderPreset {
+    /// The default rider, as in [`Entity::default_boshsled`].
+    BoshSled,
+    /// A bosh without a sled.
+    Bosh,
+    /// A sled without a bosh.
+    Sled,
+    /// A bosh and sled which are not held together by mount bones, so the bosh
+    /// tumbles freely from the start.
+    Ragdoll,
+    /// A boshsled whose sled points all grip lines as strongly as the peg, so the
+    /// sled slows down quickly when it slides along a line.
+    GrippySled,
+    /// A boshsled at half the size, with every bone scaled to match.
+    TinyRider,
+}
+
+impl RiderPreset {
+    pub const ALL: [RiderPreset; 6] = [
+        RiderPreset::BoshSled,
+        RiderPreset::Bosh,
+        RiderPreset::Sled,
+        RiderPreset::Ragdoll,
+        RiderPreset::GrippySled,
+        RiderPreset::TinyRider,
+    ];
+
+    pub fn skeleton(self) -> Skeleton {
+        match self {
+            RiderPreset::BoshSled => Skeleton::boshsled(),
+            RiderPreset::Bosh => Skeleton::bosh(),
+            RiderPreset::Sled => Skeleton::sled(),
+            RiderPreset::Ragdoll => {
+                let mut skeleton = Skeleton::boshsled();
+                skeleton
+                    .bones
+                    .retain(|bone| !matches!(bone.bone_type, BoneType::Mount { .. }));
+                // without mount bones there is nothing to dismount from, so only the
+                // joint which breaks the sled is kept
+                skeleton.joints.retain(|j| j.is_sled_joint());
+                skeleton
+            }
+            RiderPreset::GrippySled => {
+                let mut skeleton = Skeleton::boshsled();
+                for point in &mut skeleton.points {
+                    if !point.index.is_bosh() {
+                        point.friction = GRIPPY_SLED_FRICTION;
+                    }
+                }
+                skeleton
+            }
+            RiderPreset::TinyRider => Skeleton::boshsled().scaled(TINY_RIDER_SCALE),
+        }
+    }
+
+    /// Creates the preset with its skeleton's origin at `position`, moving at
+    /// `velocity`.
+    pub fn spawn(self, position: Vector2D, velocity: Vector2D) -> Entity {
+        let mut entity = Entity::from_skeleton(&self.skeleton()).expect("presets are valid");
+        entity.place(position, velocity);
+
+        entity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{Line, Track, Vector2D};
+    use crate::physics::advance_frame::frame_after;
+    use crate::physics::bone_physics::next_bone_locations;
+    use crate::rider::{BoneType, Entity, PointIndex, RiderPreset};
+    use crate::test_tracks::rider_track;
+
+    #[test]
+    fn presets_spawn_at_rest() {
+        let position = Vector2D(100.0, -20.0);
+        let velocity = Vector2D(3.0, 1.0);
+        let track = Track::new(vec![], vec![]);
+
+        for preset in RiderPreset::ALL {
+            let rider = preset.spawn(position, velocity);
+            for bone in rider.bones.iter() {
+                let length = rider
+                    .point_at(bone.p1)
+                    .location
+                    .distance_squared(rider.point_at(bone.p2).location)
+                    .sqrt();
+                assert!((length - bone.resting_length).abs() < 1e-9, "{preset:?}");
+            }
+
+            // bones at rest do not move any points
+            let next = frame_after(std::slice::from_ref(&rider), &track);
+            assert_eq!(1, next.len(), "{preset:?}");
+            for (index, point) in &rider.points {
+                let expected = point.location + velocity + track.meta.gravity;
+                let moved = next[0].point_at(*index).location;
+                assert!((moved - expected).length_squared() < 1e-18, "{preset:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn preset_shapes() {
+        let default = RiderPreset::BoshSled.spawn(Vector2D(0.0, 0.0), Vector2D(0.4, 0.0));
+        assert_eq!(Entity::default_boshsled(), default);
+        assert!(RiderPreset::Bosh
+            .spawn(Vector2D(0.0, 0.0), Vector2D(0.0, 0.0))
+            .is_bosh());
+        assert!(RiderPreset::Sled
+            .spawn(Vector2D(0.0, 0.0), Vector2D(0.0, 0.0))
+            .is_sled());
+
+        let ragdoll = RiderPreset::Ragdoll.spawn(Vector2D(0.0, 0.0), Vector2D(0.0, 0.0));
+        assert!(ragdoll.is_bosh_sled());
+        assert!(ragdoll
+            .bones
+            .iter()
+            .all(|bone| !matches!(bone.bone_type, BoneType::Mount { .. })));
+        assert!(ragdoll.joints.iter().all(|j| ragdoll.is_group_joint(j)));
+
+        let tiny = RiderPreset::TinyRider.spawn(Vector2D(0.0, 0.0), Vector2D(0.0, 0.0));
+        let height = |rider: &Entity| {
+            rider
+                .point_at(PointIndex::BoshShoulder)
+                .location
+                .distance_squared(rider.point_at(PointIndex::SledPeg).location)
+                .sqrt()
+        };
+        assert!((height(&default) * 0.5 - height(&tiny)).abs() < 1e-9);
+
+        // mount bones break at the same relative stretch at any size
+        let butt_bone = |rider: &Entity| {
+            *rider
+                .bones
+                .iter()
+                .find(|b| (b.p1, b.p2) == (PointIndex::SledPeg, PointIndex::BoshButt))
+                .unwrap()
+        };
+        for (stretch, breaks) in [(0.3, false), (0.5, true)] {
+            for rider in [&default, &tiny] {
+                let bone = butt_bone(rider);
+                let mut rider = rider.clone();
+                let peg = rider.point_at(PointIndex::SledPeg).location;
+                let butt = rider.point_at_mut(PointIndex::BoshButt);
+                butt.location = peg + (butt.location - peg) * (1.0 + stretch);
+
+                assert_eq!(breaks, next_bone_locations(&bone, &rider).is_none());
+            }
+        }
+
+        assert_eq!(
+            RiderPreset::TinyRider,
+            serde_json::from_str("\"tinyRider\"").unwrap()
+        );
+    }
+
+    #[test]
+    fn grippy_sled_slides_less() {
+        let slide = |preset: RiderPreset| {
+            let rider = preset.spawn(Vector2D(0.0, 0.0), Vector2D(4.0, 0.0));
+            let floor = Line::builder()
+                .point(-50.0, 10.0)
+                .point(1000.0, 10.0)
+                .build();
+            let track = rider_track(rider, vec![floor], |_| {});
+
+            track.entity_positions_at(60)[0]
+                .point_at(PointIndex::SledPeg)
+                .location
+                .0
+        };
+
+        assert!(slide(RiderPreset::GrippySled) < slide(RiderPreset::BoshSled) - 10.0);
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::rider::{EntityPoint, PointIndex};
+use crate::Vector2D;
+
+/// A chain of points trailing from one of an entity's points, like the scarf
+/// that linerider.com draws on the bosh. Scarves are only drawn, so they never
+/// touch lines or move the points of the entity they are attached to.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Scarf {
+    /// The point of the entity that the first segment hangs from.
+    pub anchor: PointIndex,
+    /// The points of the scarf, starting with the one closest to the anchor.
+    pub points: ScarfPoints,
+    /// The resting length of each chain bone between two consecutive points.
+    #[serde(rename = "segmentLength")]
+    pub segment_length: f64,
+    /// How much of its velocity each scarf point keeps from frame to frame.
+    #[serde(rename = "airFriction")]
+    pub air_friction: f64,
+}
+
+/// How many points a scarf can have before its points are stored on the heap.
+pub const INLINE_SCARF_POINTS: usize = 8;
+
+/// The points of a [`Scarf`].
+///
+/// Short scarves keep their points in a fixed-size array, so that entities with
+/// scarves can be cloned without allocating. Serialized as a list of points.
+#[derive(Clone)]
+pub struct ScarfPoints {
+    /// The points, if there are at most [`INLINE_SCARF_POINTS`] of them.
+    inline: [EntityPoint; INLINE_SCARF_POINTS],
+    inline_len: usize,
+    /// Every point when there are too many to fit in `inline`, otherwise empty.
+    heap: Vec<EntityPoint>,
+}
+
+const UNUSED_POINT: EntityPoint = EntityPoint {
+    previous_location: Vector2D(0.0, 0.0),
+    location: Vector2D(0.0, 0.0),
+    momentum: Vector2D(0.0, 0.0),
+    friction: 0.0,
+};
+
+impl Deref for ScarfPoints {
+    type Target = [EntityPoint];
+
+    fn deref(&self) -> &[EntityPoint] {
+        if self.heap.is_empty() {
+            &self.inline[..self.inline_len]
+        } else {
+            &self.heap
+        }
+    }
+}
+
+impl DerefMut for ScarfPoints {
+    fn deref_mut(&mut self) -> &mut [EntityPoint] {
+        if self.heap.is_empty() {
+            &mut self.inline[..self.inline_len]
+        } else {
+            &mut self.heap
+        }
+    }
+}
+
+impl From<Vec<EntityPoint>> for ScarfPoints {
+    fn from(points: Vec<EntityPoint>) -> ScarfPoints {
+        points.into_iter().collect()
+    }
+}
+
+impl FromIterator<EntityPoint> for ScarfPoints {
+    fn from_iter<T: IntoIterator<Item = EntityPoint>>(iter: T) -> ScarfPoints {
+        let mut points = ScarfPoints {
+            inline: [UNUSED_POINT; INLINE_SCARF_POINTS],
+            inline_len: 0,
+            heap: Vec::new(),
+        };
+        let mut iter = iter.into_iter();
+        for point in iter.by_ref() {
+            if points.inline_len == INLINE_SCARF_POINTS {
+                points.heap = points
+                    .inline
+                    .into_iter()
+                    .chain([point])
+                    .chain(iter)
+                    .collect();
+                points.inline_len = 0;
+                break;
+            }
+            points.inline[points.inline_len] = point;
+            points.inline_len += 1;
+        }
+
+        points
+    }
+}
+
+impl<'a> IntoIterator for &'a ScarfPoints {
+    type Item = &'a EntityPoint;
+    type IntoIter = std::slice::Iter<'a, EntityPoint>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut ScarfPoints {
+    type Item = &'a mut EntityPoint;
+    type IntoIter = std::slice::IterMut<'a, EntityPoint>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl PartialEq for ScarfPoints {
+    fn eq(&self, other: &ScarfPoints) -> bool {
+        **self == **other
+    }
+}
+
+impl fmt::Debug for ScarfPoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Serialize for ScarfPoints {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for ScarfPoints {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Vec::<EntityPoint>::deserialize(deserializer)?.into())
+    }
+}
@@ -0,0 +1,237 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use anyhow::{bail, ensure};
+use serde::{Deserialize, Serialize};
+
+use crate::game::Vector2D;
+use crate::rider::entities::{bosh, boshsled, make_entity_point, shared_bones, sled};
+use crate::rider::{Bone, BoneType, Entity, Joint, PointIndex, PointMap, Scarf};
+
+/// Describes the shape of a rider, which [`Entity::from_skeleton`] builds an entity
+/// from. Skeletons can be loaded with serde, so riders other than the boshsled can
+/// be described in data.
+///
+/// ```json
+/// {
+///   "points": [
+///     { "index": "Custom(0)", "location": [0.0, 0.0], "friction": 0.8 },
+///     { "index": "Custom(1)", "location": [10.0, 0.0] }
+///   ],
+///   "bones": [
+///     { "p1": "Custom(0)", "p2": "Custom(1)", "boneType": "Normal" }
+///   ],
+///   "breakGroups": [["Custom(0)"], ["Custom(1)"]]
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Skeleton {
+    pub points: Vec<SkeletonPoint>,
+    #[serde(default)]
+    pub bones: Vec<SkeletonBone>,
+    #[serde(default)]
+    pub joints: Vec<Joint>,
+    /// The groups of points which come apart when a mount bone breaks or a joint is
+    /// crossed. Every point must be in exactly one group. If empty, the points of the
+    /// bosh and all other points are the two groups, as for
+    /// [`Entity::break_groups`].
+    #[serde(default)]
+    pub break_groups: Vec<Vec<PointIndex>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scarf: Option<Scarf>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SkeletonPoint {
+    pub index: PointIndex,
+    pub location: Vector2D,
+    #[serde(default)]
+    pub friction: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SkeletonBone {
+    pub p1: PointIndex,
+    pub p2: PointIndex,
+    #[serde(default = "normal_bone")]
+    pub bone_type: BoneType,
+    /// Defaults to the distance between the bone's points in the skeleton.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resting_length: Option<f64>,
+}
+
+fn normal_bone() -> BoneType {
+    BoneType::Normal
+}
+
+impl Skeleton {
+    /// The skeleton of [`Entity::default_boshsled`].
+    pub fn boshsled() -> Skeleton {
+        let points = boshsled::default_points();
+        let scarf = bosh::default_scarf(&points);
+
+        let joints = boshsled::DEFAULT_JOINTS.to_vec();
+        Skeleton::from_parts(&points, &boshsled::DEFAULT_BONES, joints, Some(scarf))
+    }
+
+    /// The skeleton of [`Entity::default_bosh`].
+    pub fn bosh() -> Skeleton {
+        let points = bosh::default_points();
+        let scarf = bosh::default_scarf(&points);
+
+        Skeleton::from_parts(&points, &bosh::DEFAULT_BONES, vec![], Some(scarf))
+    }
+
+    /// The skeleton of [`Entity::default_sled`].
+    pub fn sled() -> Skeleton {
+        Skeleton::from_parts(&sled::default_points(), &sled::DEFAULT_BONES, vec![], None)
+    }
+
+    fn from_parts(
+        points: &PointMap,
+        bones: &[Bone],
+        joints: Vec<Joint>,
+        scarf: Option<Scarf>,
+    ) -> Skeleton {
+        Skeleton {
+            points: points
+                .iter()
+                .map(|(&index, point)| SkeletonPoint {
+                    index,
+                    location: point.location,
+                    friction: point.friction,
+                })
+                .collect(),
+            bones: bones
+                .iter()
+                .map(|bone| SkeletonBone {
+                    p1: bone.p1,
+                    p2: bone.p2,
+                    bone_type: bone.bone_type,
+                    resting_length: Some(bone.resting_length),
+                })
+                .collect(),
+            joints,
+            break_groups: vec![],
+            scarf,
+        }
+    }
+
+    /// Returns the skeleton scaled around its origin, with its resting lengths and
+    /// scarf scaled to match. Mount bones break at the same stretch relative to their
+    /// length as before.
+    pub fn scaled(mut self, factor: f64) -> Skeleton {
+        for point in &mut self.points {
+            point.location *= factor;
+        }
+        for bone in &mut self.bones {
+            bone.resting_length = bone.resting_length.map(|length| length * factor);
+            // a mount bone breaks once its stretch passes `endurance` times its
+            // resting length, so the endurance scales the other way
+            if let BoneType::Mount { endurance } = &mut bone.bone_type {
+                *endurance /= factor;
+            }
+        }
+        if let Some(scarf) = &mut self.scarf {
+            for point in &mut scarf.points {
+                point.location *= factor;
+                point.previous_location *= factor;
+                point.momentum *= factor;
+            }
+            scarf.segment_length *= factor;
+        }
+
+        self
+    }
+
+    /// Checks that every bone, joint, break group and scarf refers to points of
+    /// the skeleton, and that no point is defined twice or put in two groups.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut indices = HashSet::new();
+        for point in &self.points {
+            ensure!(
+                indices.insert(point.index),
+                "point {} is defined twice",
+                point.index
+            );
+        }
+        let check = |index: &PointIndex, used_by: &str| {
+            ensure!(
+                indices.contains(index),
+                "{used_by} uses undefined point {index}"
+            );
+            Ok(())
+        };
+
+        for bone in &self.bones {
+            check(&bone.p1, "bone")?;
+            check(&bone.p2, "bone")?;
+        }
+        for joint in &self.joints {
+            for index in [joint.pair1.0, joint.pair1.1, joint.pair2.0, joint.pair2.1] {
+                check(&index, "joint")?;
+            }
+        }
+        if let Some(scarf) = &self.scarf {
+            check(&scarf.anchor, "scarf")?;
+        }
+
+        if self.break_groups.is_empty() {
+            return Ok(());
+        }
+        let mut grouped = HashSet::new();
+        for index in self.break_groups.iter().flatten() {
+            check(index, "break group")?;
+            ensure!(
+                grouped.insert(*index),
+                "point {index} is in two break groups"
+            );
+        }
+        if let Some(point) = self.points.iter().find(|p| !grouped.contains(&p.index)) {
+            bail!("point {} is not in any break group", point.index);
+        }
+
+        Ok(())
+    }
+}
+
+impl Entity {
+    /// Builds an entity from a skeleton. Its points start out moving at the same
+    /// velocity as the points of [`Entity::default_boshsled`].
+    pub fn from_skeleton(skeleton: &Skeleton) -> anyhow::Result<Entity> {
+        skeleton.validate()?;
+
+        let points: PointMap = skeleton
+            .points
+            .iter()
+            .map(|p| (p.index, make_entity_point(p.location, p.friction)))
+            .collect();
+        let bones = skeleton
+            .bones
+            .iter()
+            .map(|bone| Bone {
+                p1: bone.p1,
+                p2: bone.p2,
+                resting_length: bone.resting_length.unwrap_or_else(|| {
+                    points[&bone.p1]
+                        .location
+                        .distance_squared(points[&bone.p2].location)
+                        .sqrt()
+                }),
+                bone_type: bone.bone_type,
+            })
+            .collect();
+        Ok(Entity {
+            id: Default::default(),
+            parent: None,
+            points,
+            bones: shared_bones(bones),
+            joints: Cow::Owned(skeleton.joints.clone()),
+            mount_state: Default::default(),
+            scarf: skeleton.scarf.clone(),
+            break_groups: skeleton.break_groups.as_slice().into(),
+        })
+    }
+}
@@ -0,0 +1,66 @@
+//! Tracks shared by the tests of several modules.
+
+use crate::game::{Line, Track, TrackMeta, Vector2D};
+use crate::rider::Entity;
+
+/// A track with a single rider, whose meta starts out as the default and is then
+/// changed by `configure`.
+pub(crate) fn rider_track(
+    rider: Entity,
+    lines: Vec<Line>,
+    configure: impl FnOnce(&mut TrackMeta),
+) -> Track {
+    let mut meta = TrackMeta::default();
+    configure(&mut meta);
+
+    Track::new_with_meta(vec![rider], lines, meta)
+}
+
+/// A default boshsled moving right at 2.4 units per frame over a long, flat floor.
+pub(crate) fn floor_track() -> Track {
+    let mut entity = Entity::default_boshsled();
+    entity.mutate_points(|p| {
+        p.previous_location -= Vector2D(2.0, 0.0);
+        p.momentum += Vector2D(2.0, 0.0);
+    });
+
+    rider_track(
+        entity,
+        vec![Line::builder()
+            .point(-50.0, 10.0)
+            .point(1000.0, 10.0)
+            .build()],
+        |_| {},
+    )
+}
+
+/// A default boshsled moving right fast enough to crash into a wall at x = 60.
+pub(crate) fn wall_crash_track(remount: bool) -> Track {
+    let mut entity = Entity::default_boshsled();
+    entity.mutate_points(|p| {
+        p.previous_location -= Vector2D(12.0, 0.0);
+        p.momentum += Vector2D(12.0, 0.0);
+    });
+
+    rider_track(
+        entity,
+        vec![
+            Line::builder()
+                .point(-50.0, 10.0)
+                .point(300.0, 10.0)
+                .build(),
+            Line::builder().point(60.0, 10.0).point(60.0, -20.0).build(),
+        ],
+        |meta| meta.remount = remount,
+    )
+}
+
+/// The average distance that the points of `entity` moved in its last frame.
+pub(crate) fn avg_velocity(entity: &Entity) -> Vector2D {
+    let sum: Vector2D = entity
+        .points
+        .values()
+        .map(|p| p.location - p.previous_location)
+        .sum();
+    sum / entity.points.len() as f64
+}